@@ -0,0 +1,6 @@
+CREATE TABLE users (
+	user_id uuid PRIMARY KEY,
+	username TEXT NOT NULL UNIQUE,
+	password_hash TEXT NOT NULL,
+	role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer'))
+);
@@ -0,0 +1,7 @@
+CREATE TABLE user_sessions (
+	session_id_hash TEXT NOT NULL,
+	user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL,
+	expires_at timestamptz NOT NULL,
+	PRIMARY KEY(session_id_hash)
+);
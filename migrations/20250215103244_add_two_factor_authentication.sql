@@ -0,0 +1,20 @@
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+ALTER TABLE users ADD COLUMN totp_pending_secret TEXT NULL;
+ALTER TABLE users ADD COLUMN totp_last_used_step BIGINT NULL;
+
+ALTER TABLE user_sessions ADD COLUMN second_factor_pending BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE user_sessions ADD COLUMN second_factor_attempts INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE recovery_codes (
+	user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+	code_hash TEXT NOT NULL,
+	used_at timestamptz NULL,
+	PRIMARY KEY(user_id, code_hash)
+);
+
+-- Deployment-wide security settings, always exactly one row.
+CREATE TABLE security_policy (
+	id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+	require_two_factor BOOLEAN NOT NULL
+);
+INSERT INTO security_policy (require_two_factor) VALUES (FALSE);
@@ -0,0 +1,12 @@
+ALTER TABLE users ADD COLUMN email TEXT NULL;
+-- Reset requests look users up by email whatever its case.
+CREATE UNIQUE INDEX users_email_key ON users (lower(email));
+
+CREATE TABLE password_reset_tokens (
+	token_hash TEXT NOT NULL,
+	user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL,
+	expires_at timestamptz NOT NULL,
+	used_at timestamptz NULL,
+	PRIMARY KEY(token_hash)
+);
@@ -0,0 +1,24 @@
+CREATE TABLE audit_log (
+	id BIGSERIAL PRIMARY KEY,
+	occurred_at timestamptz NOT NULL,
+	actor_type TEXT NOT NULL,
+	actor_id uuid NULL,
+	action TEXT NOT NULL,
+	target_type TEXT NOT NULL,
+	target_id TEXT NOT NULL,
+	request_id TEXT NULL,
+	before JSONB NULL,
+	after JSONB NULL
+);
+CREATE INDEX audit_log_target_idx ON audit_log (target_type, target_id);
+
+-- Entries can be added, never changed or removed.
+CREATE FUNCTION reject_audit_log_changes() RETURNS trigger AS $$
+BEGIN
+	RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_is_append_only
+	BEFORE UPDATE OR DELETE ON audit_log
+	FOR EACH ROW EXECUTE FUNCTION reject_audit_log_changes();
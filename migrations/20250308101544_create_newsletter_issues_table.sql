@@ -0,0 +1,19 @@
+-- Issues are drafted first and delivered to every confirmed subscriber once published
+CREATE TABLE newsletter_issues (
+	issue_id uuid NOT NULL,
+	PRIMARY KEY(issue_id),
+	title TEXT NOT NULL,
+	text_content TEXT NOT NULL,
+	html_content TEXT NOT NULL,
+	created_by uuid NOT NULL REFERENCES users (user_id),
+	created_at timestamptz NOT NULL,
+	published_at timestamptz NULL
+);
+
+CREATE TABLE issue_deliveries (
+	issue_id uuid NOT NULL REFERENCES newsletter_issues (issue_id),
+	subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+	outcome TEXT NOT NULL CHECK (outcome IN ('sent', 'failed')),
+	attempted_at timestamptz NOT NULL,
+	PRIMARY KEY(issue_id, subscriber_id)
+);
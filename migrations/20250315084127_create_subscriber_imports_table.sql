@@ -0,0 +1,18 @@
+-- Bulk imports of subscribers from CSV files, with the rows that could not be imported
+CREATE TABLE subscriber_imports (
+	import_id uuid NOT NULL,
+	PRIMARY KEY(import_id),
+	created_by uuid NOT NULL REFERENCES users (user_id),
+	created_at timestamptz NOT NULL,
+	completed_at timestamptz NULL,
+	imported_rows INT NOT NULL DEFAULT 0,
+	failed_rows INT NOT NULL DEFAULT 0
+);
+
+-- Only the line number and the reason are kept, never the rejected values
+CREATE TABLE subscriber_import_errors (
+	import_id uuid NOT NULL REFERENCES subscriber_imports (import_id) ON DELETE CASCADE,
+	line BIGINT NOT NULL,
+	message TEXT NOT NULL,
+	PRIMARY KEY(import_id, line)
+);
@@ -0,0 +1,2 @@
+-- When the subscriber confirmed their address, unknown for subscribers confirmed before this column existed
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
@@ -0,0 +1,8 @@
+-- Magic links letting subscribers download the data held about them
+CREATE TABLE data_export_tokens (
+	token_hash TEXT NOT NULL,
+	PRIMARY KEY(token_hash),
+	subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL,
+	expires_at timestamptz NOT NULL
+);
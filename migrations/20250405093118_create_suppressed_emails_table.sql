@@ -0,0 +1,8 @@
+-- Salted hashes of erased addresses, so that they are not subscribed again
+CREATE TABLE suppressed_emails (
+	email_hash TEXT NOT NULL,
+	PRIMARY KEY(email_hash),
+	suppressed_at timestamptz NOT NULL
+);
+-- Erased subscribers keep their row, stripped of anything identifying, for the delivery statistics
+ALTER TABLE subscriptions ADD COLUMN erased_at timestamptz NULL;
@@ -0,0 +1,14 @@
+-- How each subscriber opted in, kept as proof of consent
+CREATE TABLE consents (
+	consent_id uuid NOT NULL,
+	PRIMARY KEY(consent_id),
+	subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+	event TEXT NOT NULL CHECK (event IN ('subscribe', 'confirm')),
+	ip_address TEXT NULL,
+	user_agent TEXT NULL,
+	-- The form or page the subscription came from, as reported by the form
+	source TEXT NULL,
+	consent_text_version TEXT NULL,
+	recorded_at timestamptz NOT NULL
+);
+CREATE INDEX consents_subscriber_id_idx ON consents (subscriber_id, recorded_at);
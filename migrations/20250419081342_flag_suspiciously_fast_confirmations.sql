@@ -0,0 +1,3 @@
+-- When the confirmation email was sent, to tell link scanners from people
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE consents ADD COLUMN suspiciously_fast BOOLEAN NOT NULL DEFAULT false;
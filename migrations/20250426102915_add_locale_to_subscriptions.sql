@@ -0,0 +1,2 @@
+-- The language emails are sent in, the default locale when unset
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NULL;
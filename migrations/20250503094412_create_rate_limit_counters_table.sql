@@ -0,0 +1,7 @@
+-- Request counters shared by every replica when rate limits are kept in Postgres
+CREATE TABLE rate_limit_counters (
+	key TEXT NOT NULL,
+	PRIMARY KEY(key),
+	window_ends_at timestamptz NOT NULL,
+	count INTEGER NOT NULL
+);
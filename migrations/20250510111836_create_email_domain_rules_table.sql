@@ -0,0 +1,9 @@
+-- Domains admins want refused or accepted regardless of the bundled disposable list
+CREATE TABLE email_domain_rules (
+	domain TEXT NOT NULL,
+	PRIMARY KEY(domain),
+	rule TEXT NOT NULL CHECK (rule IN ('block', 'allow')),
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+-- Why a signup was let in but deserves a second look, NULL for most
+ALTER TABLE subscriptions ADD COLUMN flagged_reason TEXT NULL;
@@ -0,0 +1,25 @@
+-- The address in the form subscribers are unique on, with its domain lowercased.
+-- Only the application converts internationalised domains to punycode, so
+-- existing rows keep theirs in Unicode.
+ALTER TABLE subscriptions ADD COLUMN normalised_email TEXT NULL;
+UPDATE subscriptions
+SET normalised_email = substring(email from '^(.*)@[^@]*$') || '@' || lower(substring(email from '@([^@]*)$'));
+
+-- Subscribers who signed up twice with different casing have to be merged by hand first
+DO $$
+DECLARE
+	collisions BIGINT;
+BEGIN
+	SELECT COUNT(*) INTO collisions FROM (
+		SELECT normalised_email FROM subscriptions
+		GROUP BY normalised_email
+		HAVING COUNT(*) > 1
+	) AS duplicates;
+	IF collisions > 0 THEN
+		RAISE EXCEPTION '% normalised email addresses are shared by several subscribers', collisions
+			USING HINT = 'List them with: SELECT normalised_email, array_agg(id) FROM subscriptions GROUP BY normalised_email HAVING COUNT(*) > 1';
+	END IF;
+END $$;
+
+ALTER TABLE subscriptions ALTER COLUMN normalised_email SET NOT NULL;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_normalised_email_key UNIQUE (normalised_email);
@@ -0,0 +1,39 @@
+-- The newsletters people can subscribe to, one of them taking the signups of /subscriptions
+CREATE TABLE lists (
+	list_id uuid NOT NULL,
+	PRIMARY KEY(list_id),
+	slug TEXT NOT NULL UNIQUE,
+	name TEXT NOT NULL,
+	is_default BOOLEAN NOT NULL DEFAULT false,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX lists_single_default_idx ON lists (is_default) WHERE is_default;
+INSERT INTO lists (list_id, slug, name, is_default) VALUES (gen_random_uuid(), 'newsletter', 'Newsletter', true);
+
+-- Which lists each subscriber joined, each confirmed on its own
+CREATE TABLE list_memberships (
+	list_id uuid NOT NULL REFERENCES lists (list_id),
+	subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+	PRIMARY KEY(list_id, subscriber_id),
+	status TEXT NOT NULL CHECK (status IN ('pending_confirmation', 'confirmed')),
+	subscribed_at timestamptz NOT NULL,
+	confirmed_at timestamptz NULL
+);
+CREATE INDEX list_memberships_subscriber_id_idx ON list_memberships (subscriber_id);
+INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at, confirmed_at)
+SELECT lists.list_id, subscriptions.id, subscriptions.status, subscriptions.subscribed_at, subscriptions.confirmed_at
+FROM subscriptions, lists
+WHERE lists.is_default AND subscriptions.status IN ('pending_confirmation', 'confirmed');
+
+-- Tokens, consents and issues so far were all about the default list
+ALTER TABLE subscription_tokens ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+UPDATE subscription_tokens SET list_id = (SELECT list_id FROM lists WHERE is_default);
+ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
+
+ALTER TABLE consents ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+UPDATE consents SET list_id = (SELECT list_id FROM lists WHERE is_default);
+ALTER TABLE consents ALTER COLUMN list_id SET NOT NULL;
+
+ALTER TABLE newsletter_issues ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+UPDATE newsletter_issues SET list_id = (SELECT list_id FROM lists WHERE is_default);
+ALTER TABLE newsletter_issues ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,27 @@
+-- The subjects an issue of a list can be about
+CREATE TABLE topics (
+	topic_id uuid NOT NULL,
+	PRIMARY KEY(topic_id),
+	list_id uuid NOT NULL REFERENCES lists (list_id),
+	slug TEXT NOT NULL,
+	name TEXT NOT NULL,
+	created_at timestamptz NOT NULL DEFAULT now(),
+	UNIQUE (list_id, slug)
+);
+
+-- The topics each subscriber turned off; they receive the others, new ones included
+CREATE TABLE topic_opt_outs (
+	subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+	topic_id uuid NOT NULL REFERENCES topics (topic_id),
+	PRIMARY KEY(subscriber_id, topic_id),
+	opted_out_at timestamptz NOT NULL
+);
+
+-- Issues about some topics only, for the subscribers who receive one of them
+CREATE TABLE issue_topics (
+	issue_id uuid NOT NULL REFERENCES newsletter_issues (issue_id),
+	topic_id uuid NOT NULL REFERENCES topics (topic_id),
+	PRIMARY KEY(issue_id, topic_id)
+);
+
+ALTER TABLE subscriptions ADD COLUMN delivery_paused_at timestamptz NULL;
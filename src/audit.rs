@@ -0,0 +1,97 @@
+use std::convert::Infallible;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use sqlx::PgExecutor;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Who performed an audited action.
+#[derive(Clone, Copy, Debug)]
+pub enum Actor {
+    User(Uuid),
+    Subscriber(Uuid),
+}
+
+impl Actor {
+    fn kind(&self) -> &'static str {
+        match self {
+            Actor::User(_) => "user",
+            Actor::Subscriber(_) => "subscriber",
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        match self {
+            Actor::User(id) | Actor::Subscriber(id) => *id,
+        }
+    }
+}
+
+/// A change worth answering "who changed what, when" for.
+///
+/// `before` and `after` must not contain subscriber PII (email, name): the
+/// log is append-only, so it could not be erased on request. Refer to
+/// subscribers through their id instead.
+pub struct AuditEntry {
+    pub actor: Actor,
+    pub action: &'static str,
+    pub target_type: &'static str,
+    pub target_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// The id assigned to the current request by the request id middleware.
+#[derive(Clone, Debug, Default)]
+pub struct RequestId(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = parts
+            .extensions
+            .get::<tower_http::request_id::RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(str::to_owned);
+
+        Ok(Self(request_id))
+    }
+}
+
+/// Append `entry` to the audit log.
+///
+/// Pass the transaction that performs the change, so that the change and
+/// its audit entry are committed together.
+#[tracing::instrument(
+    name = "Record an audit entry",
+    skip(executor, request_id, entry),
+    fields(action = entry.action, target_id = %entry.target_id)
+)]
+pub async fn record_audit_entry<'e>(
+    executor: impl PgExecutor<'e>,
+    request_id: &RequestId,
+    entry: AuditEntry,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO audit_log
+        (occurred_at, actor_type, actor_id, action, target_type, target_id, request_id, before, after)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+        OffsetDateTime::now_utc(),
+        entry.actor.kind(),
+        entry.actor.id(),
+        entry.action,
+        entry.target_type,
+        entry.target_id,
+        request_id.0,
+        entry.before,
+        entry.after,
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
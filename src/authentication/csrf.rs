@@ -0,0 +1,75 @@
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+
+use super::token::generate_token;
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+
+/// Return the CSRF token of this browser, issuing a new one if it has none.
+///
+/// The token is kept in a cookie and must be echoed back in a hidden form
+/// field: another site can make the browser send the cookie, but it cannot
+/// read it to fill in the field.
+pub fn csrf_token(jar: CookieJar) -> (CookieJar, String) {
+    if let Some(cookie) = jar.get(CSRF_COOKIE) {
+        let token = cookie.value().to_owned();
+        return (jar, token);
+    }
+
+    let token = generate_token(32);
+    let cookie = Cookie::build((CSRF_COOKIE, token.clone()))
+        .path("/admin")
+        .http_only(true)
+        .same_site(SameSite::Strict);
+    (jar.add(cookie), token)
+}
+
+/// Check the token submitted with a form against the one in the cookie.
+pub fn verify_csrf_token(jar: &CookieJar, submitted: &str) -> bool {
+    let Some(cookie) = jar.get(CSRF_COOKIE) else {
+        return false;
+    };
+    let expected = cookie.value().as_bytes();
+    let submitted = submitted.as_bytes();
+
+    // Compare in constant time, so that the token cannot be guessed byte by byte.
+    expected.len() == submitted.len()
+        && expected
+            .iter()
+            .zip(submitted)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_issued_token_is_accepted() {
+        let (jar, token) = csrf_token(CookieJar::new());
+
+        assert!(verify_csrf_token(&jar, &token));
+    }
+
+    #[test]
+    fn an_existing_token_is_reused() {
+        let (jar, token) = csrf_token(CookieJar::new());
+        let (_, second_token) = csrf_token(jar);
+
+        assert_eq!(token, second_token);
+    }
+
+    #[test]
+    fn other_tokens_are_rejected() {
+        let (jar, token) = csrf_token(CookieJar::new());
+        let (_, other_token) = csrf_token(CookieJar::new());
+
+        assert!(!verify_csrf_token(&jar, ""));
+        assert!(!verify_csrf_token(&jar, &token[1..]));
+        assert!(!verify_csrf_token(&jar, &other_token));
+        assert!(!verify_csrf_token(&CookieJar::new(), &token));
+    }
+}
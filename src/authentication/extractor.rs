@@ -0,0 +1,65 @@
+use axum::{extract::FromRequestParts, http::request::Parts, http::StatusCode};
+use axum_extra::extract::CookieJar;
+use uuid::Uuid;
+
+use crate::{authorization::Role, startup::AppState};
+
+use super::{get_session_user, SESSION_COOKIE};
+
+/// The user behind the session cookie of the current request.
+///
+/// Rejects with a 401 when the cookie is missing, unknown or expired, or when
+/// the session is still waiting for its second factor.
+///
+/// The user is cached in the request extensions, so extracting it again
+/// further down the stack does not hit the database twice.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+    /// The security policy requires two-factor authentication and this user
+    /// has not enrolled yet.
+    pub must_enroll_two_factor: bool,
+}
+
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<Self>() {
+            return Ok(user.clone());
+        }
+
+        let jar = CookieJar::from_headers(&parts.headers);
+        let Some(session_id) = jar.get(SESSION_COOKIE) else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+
+        let Ok(session_user) = get_session_user(&state.database, session_id.value()).await else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+
+        let Some(session_user) = session_user.filter(|s| !s.second_factor_pending) else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+
+        let Ok(role) = Role::try_from(session_user.role) else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+
+        let user = Self {
+            user_id: session_user.user_id,
+            username: session_user.username,
+            role,
+            must_enroll_two_factor: session_user.two_factor_required
+                && !session_user.two_factor_enabled,
+        };
+        parts.extensions.insert(user.clone());
+
+        Ok(user)
+    }
+}
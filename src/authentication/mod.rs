@@ -0,0 +1,24 @@
+mod csrf;
+mod extractor;
+mod password;
+mod password_reset;
+mod session;
+mod token;
+mod two_factor;
+
+pub use csrf::{csrf_token, verify_csrf_token, CSRF_COOKIE};
+pub use extractor::AuthenticatedUser;
+pub use password::{
+    bootstrap_owner, compute_password_hash, validate_credentials, validate_new_password, AuthError,
+    Credentials,
+};
+pub use password_reset::{issue_password_reset_token, reset_password, PasswordResetToken};
+pub use session::{
+    complete_second_factor, create_session, delete_session, get_session_user,
+    record_failed_second_factor, session_cookie, SESSION_COOKIE,
+};
+pub use token::{generate_token, hash_token};
+pub use two_factor::{
+    confirm_totp_enrollment, is_two_factor_enabled, start_totp_enrollment, verify_second_factor,
+    TotpEnrollment,
+};
@@ -0,0 +1,140 @@
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{authorization::Role, configuration::AdminSettings};
+
+pub struct Credentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidCredentials,
+    Unexpected(String),
+}
+
+// Verified against when the username is unknown, so that both failure
+// modes take roughly the same amount of time.
+const FALLBACK_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+    gZiV/M1gPc22ElAH/Jh1Hw$\
+    CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, database))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    database: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    let mut expected_password_hash = SecretString::from(FALLBACK_PASSWORD_HASH);
+
+    if let Some((stored_user_id, stored_password_hash)) =
+        get_stored_credentials(&credentials.username, database)
+            .await
+            .map_err(|e| AuthError::Unexpected(e.to_string()))?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    tokio::task::spawn_blocking(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))??;
+
+    user_id.ok_or(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(username, database))]
+async fn get_stored_credentials(
+    username: &str,
+    database: &PgPool,
+) -> Result<Option<(Uuid, SecretString)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id, password_hash FROM users WHERE username = $1"#,
+        username,
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(row.map(|r| (r.user_id, SecretString::from(r.password_hash))))
+}
+
+fn verify_password_hash(
+    expected_password_hash: SecretString,
+    password_candidate: SecretString,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .map_err(|_| AuthError::InvalidCredentials)
+}
+
+/// Check that a password chosen by a user is acceptable.
+pub fn validate_new_password(password: &SecretString) -> Result<(), String> {
+    let length = password.expose_secret().chars().count();
+    if !(12..=128).contains(&length) {
+        return Err("passwords must be between 12 and 128 characters long".into());
+    }
+    Ok(())
+}
+
+pub fn compute_password_hash(password: SecretString) -> Result<SecretString, String> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let params = Params::new(15000, 2, 1, None).map_err(|e| e.to_string())?;
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map_err(|e| e.to_string())?
+        .to_string();
+
+    Ok(SecretString::from(password_hash))
+}
+
+/// Create the configured owner account if nobody can log in yet.
+#[tracing::instrument(name = "Bootstrap the owner account", skip(database, settings))]
+pub async fn bootstrap_owner(database: &PgPool, settings: &AdminSettings) -> Result<(), String> {
+    let user_count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM users"#)
+        .fetch_one(database)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if user_count > 0 {
+        return Ok(());
+    }
+
+    let password = settings.password.clone();
+    let password_hash = tokio::task::spawn_blocking(move || compute_password_hash(password))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    sqlx::query!(
+        r#"INSERT INTO users (user_id, username, email, password_hash, role)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        Uuid::new_v4(),
+        settings.username,
+        settings.email,
+        password_hash.expose_secret(),
+        Role::Owner.as_str(),
+    )
+    .execute(database)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    tracing::info!(username = %settings.username, "created the initial owner account");
+    Ok(())
+}
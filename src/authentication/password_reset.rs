@@ -0,0 +1,111 @@
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{PgPool, Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{
+    compute_password_hash,
+    token::{generate_token, hash_token},
+    AuthError,
+};
+
+const RESET_TOKEN_LIFETIME: Duration = Duration::minutes(30);
+
+pub struct PasswordResetToken {
+    pub user_id: Uuid,
+    pub token: String,
+}
+
+/// Issue a single-use reset token for the user registered with `email`, if any.
+///
+/// Only a hash of the token is persisted.
+#[tracing::instrument(name = "Issue a password reset token", skip(database, email))]
+pub async fn issue_password_reset_token(
+    database: &PgPool,
+    email: &str,
+) -> Result<Option<PasswordResetToken>, sqlx::Error> {
+    let user_id = sqlx::query_scalar!(
+        r#"SELECT user_id FROM users WHERE lower(email) = lower($1)"#,
+        email,
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    let token = generate_token(32);
+    let now = OffsetDateTime::now_utc();
+    sqlx::query!(
+        r#"INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)"#,
+        hash_token(&token),
+        user_id,
+        now,
+        now + RESET_TOKEN_LIFETIME,
+    )
+    .execute(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(Some(PasswordResetToken { user_id, token }))
+}
+
+/// Set a new password for the owner of `token`, consuming the token.
+///
+/// Every session of the user is ended and any other outstanding reset
+/// token is discarded. Returns the id of the user whose password changed.
+#[tracing::instrument(name = "Reset a password", skip(transaction, token, new_password))]
+pub async fn reset_password(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+    new_password: SecretString,
+) -> Result<Uuid, AuthError> {
+    let user_id = sqlx::query_scalar!(
+        r#"UPDATE password_reset_tokens SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id"#,
+        hash_token(token),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?
+    .ok_or(AuthError::InvalidCredentials)?;
+
+    let password_hash = tokio::task::spawn_blocking(move || compute_password_hash(new_password))
+        .await
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?
+        .map_err(AuthError::Unexpected)?;
+
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    sqlx::query!(
+        r#"DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL"#,
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    sqlx::query!(r#"DELETE FROM user_sessions WHERE user_id = $1"#, user_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    Ok(user_id)
+}
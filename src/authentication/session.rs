@@ -0,0 +1,156 @@
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use super::token::{generate_token, hash_token};
+
+pub const SESSION_COOKIE: &str = "session_id";
+
+const SESSION_LIFETIME: Duration = Duration::hours(12);
+
+/// Wrong second factor codes tolerated before a pending session is dropped.
+const MAX_SECOND_FACTOR_ATTEMPTS: i32 = 5;
+
+pub struct SessionUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: String,
+    pub second_factor_pending: bool,
+    pub two_factor_enabled: bool,
+    pub two_factor_required: bool,
+}
+
+/// Start a new session for `user_id`, returning the token to hand to the client.
+///
+/// Only a hash of the token is persisted. A session created with
+/// `second_factor_pending` is not accepted by `AuthenticatedUser` until
+/// `complete_second_factor` is called.
+#[tracing::instrument(name = "Create a new session", skip(database))]
+pub async fn create_session(
+    database: &PgPool,
+    user_id: Uuid,
+    second_factor_pending: bool,
+) -> Result<String, sqlx::Error> {
+    let session_id = generate_token(32);
+    let now = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        r#"INSERT INTO user_sessions
+        (session_id_hash, user_id, created_at, expires_at, second_factor_pending)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        hash_token(&session_id),
+        user_id,
+        now,
+        now + SESSION_LIFETIME,
+        second_factor_pending,
+    )
+    .execute(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(session_id)
+}
+
+/// The cookie carrying `session_id`, scoped to the admin routes.
+pub fn session_cookie(session_id: String) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, session_id))
+        .path("/admin")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .build()
+}
+
+#[tracing::instrument(name = "Get the user of a session", skip(database, session_id))]
+pub async fn get_session_user(
+    database: &PgPool,
+    session_id: &str,
+) -> Result<Option<SessionUser>, sqlx::Error> {
+    sqlx::query_as!(
+        SessionUser,
+        r#"SELECT users.user_id, users.username, users.role,
+            user_sessions.second_factor_pending,
+            users.totp_secret IS NOT NULL AS "two_factor_enabled!",
+            (SELECT require_two_factor FROM security_policy) AS "two_factor_required!"
+        FROM user_sessions
+        JOIN users ON users.user_id = user_sessions.user_id
+        WHERE user_sessions.session_id_hash = $1 AND user_sessions.expires_at > now()"#,
+        hash_token(session_id),
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+#[tracing::instrument(name = "Delete a session", skip(database, session_id))]
+pub async fn delete_session(database: &PgPool, session_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM user_sessions WHERE session_id_hash = $1"#,
+        hash_token(session_id),
+    )
+    .execute(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Complete the second factor of a session",
+    skip(database, session_id)
+)]
+pub async fn complete_second_factor(
+    database: &PgPool,
+    session_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE user_sessions SET second_factor_pending = FALSE WHERE session_id_hash = $1"#,
+        hash_token(session_id),
+    )
+    .execute(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+/// Count a wrong second factor code against a pending session, dropping the
+/// session once too many attempts were made.
+#[tracing::instrument(
+    name = "Record a failed second factor attempt",
+    skip(database, session_id)
+)]
+pub async fn record_failed_second_factor(
+    database: &PgPool,
+    session_id: &str,
+) -> Result<(), sqlx::Error> {
+    let session_id_hash = hash_token(session_id);
+    let attempts = sqlx::query_scalar!(
+        r#"UPDATE user_sessions SET second_factor_attempts = second_factor_attempts + 1
+        WHERE session_id_hash = $1
+        RETURNING second_factor_attempts"#,
+        session_id_hash,
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    if attempts.is_some_and(|attempts| attempts >= MAX_SECOND_FACTOR_ATTEMPTS) {
+        tracing::warn!("too many failed second factor attempts, dropping the session");
+        delete_session(database, session_id).await?;
+    }
+    Ok(())
+}
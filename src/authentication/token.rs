@@ -0,0 +1,18 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+/// A random alphanumeric token, suitable for cookies and links.
+pub fn generate_token(length: usize) -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(length)
+        .collect()
+}
+
+/// The form in which tokens are persisted.
+///
+/// Tokens carry enough entropy that a fast hash is sufficient, unlike passwords.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
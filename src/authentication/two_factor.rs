@@ -0,0 +1,313 @@
+use qrcode::{render::svg, QrCode};
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use super::{
+    token::{generate_token, hash_token},
+    AuthError,
+};
+
+const TOTP_ISSUER: &str = "zero2prod";
+const TOTP_STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_code_svg: String,
+}
+
+#[tracing::instrument(
+    name = "Check whether two-factor authentication is enabled",
+    skip(database)
+)]
+pub async fn is_two_factor_enabled(database: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT totp_secret IS NOT NULL AS "enabled!" FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_one(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// Generate a fresh TOTP secret for `username` and store it as pending.
+///
+/// The secret only protects the account once `confirm_totp_enrollment`
+/// has seen a valid code for it.
+#[tracing::instrument(name = "Start TOTP enrollment", skip(database))]
+pub async fn start_totp_enrollment(
+    database: &PgPool,
+    user_id: Uuid,
+    username: &str,
+) -> Result<TotpEnrollment, AuthError> {
+    let Secret::Encoded(secret) = Secret::generate_secret().to_encoded() else {
+        unreachable!("`to_encoded` always returns an encoded secret");
+    };
+    let totp = totp(&secret, username).map_err(AuthError::Unexpected)?;
+    let otpauth_uri = totp.get_url();
+    let qr_code_svg = QrCode::new(otpauth_uri.as_bytes())
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+
+    sqlx::query!(
+        r#"UPDATE users SET totp_pending_secret = $1 WHERE user_id = $2"#,
+        secret,
+        user_id,
+    )
+    .execute(database)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    Ok(TotpEnrollment {
+        secret,
+        otpauth_uri,
+        qr_code_svg,
+    })
+}
+
+/// Activate the pending TOTP secret of a user if `code` matches it.
+///
+/// Returns a new set of recovery codes, replacing any previous ones. Only
+/// their hashes are stored, so this is the one time they can be shown.
+#[tracing::instrument(name = "Confirm TOTP enrollment", skip(transaction, code))]
+pub async fn confirm_totp_enrollment(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    username: &str,
+    code: &str,
+) -> Result<Vec<String>, AuthError> {
+    let pending_secret = sqlx::query_scalar!(
+        r#"SELECT totp_pending_secret FROM users WHERE user_id = $1 FOR UPDATE"#,
+        user_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?
+    .ok_or(AuthError::InvalidCredentials)?;
+
+    let step = verify_totp_code(&pending_secret, username, code, unix_now())
+        .map_err(AuthError::Unexpected)?
+        .ok_or(AuthError::InvalidCredentials)?;
+
+    sqlx::query!(
+        r#"UPDATE users
+        SET totp_secret = totp_pending_secret, totp_pending_secret = NULL, totp_last_used_step = $1
+        WHERE user_id = $2"#,
+        step as i64,
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    let recovery_codes: Vec<String> = std::iter::repeat_with(generate_recovery_code)
+        .take(RECOVERY_CODE_COUNT)
+        .collect();
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| hash_recovery_code(c))
+        .collect();
+    sqlx::query!(
+        r#"INSERT INTO recovery_codes (user_id, code_hash)
+        SELECT $1, * FROM UNNEST($2::text[])"#,
+        user_id,
+        &code_hashes,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    Ok(recovery_codes)
+}
+
+/// Check a second factor `code`, which is either the current TOTP code or
+/// one of the unused recovery codes of the user.
+///
+/// Both are single use: a TOTP code cannot be replayed within its window
+/// and a recovery code is burnt once accepted.
+#[tracing::instrument(name = "Verify second factor", skip(database, code))]
+pub async fn verify_second_factor(
+    database: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<(), AuthError> {
+    let code = code.trim();
+    if code.contains('-') {
+        return use_recovery_code(database, user_id, code).await;
+    }
+
+    let row = sqlx::query!(
+        r#"SELECT username, totp_secret FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_one(database)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+    let secret = row.totp_secret.ok_or(AuthError::InvalidCredentials)?;
+
+    let step = verify_totp_code(&secret, &row.username, code, unix_now())
+        .map_err(AuthError::Unexpected)?
+        .ok_or(AuthError::InvalidCredentials)?;
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET totp_last_used_step = $1
+        WHERE user_id = $2 AND (totp_last_used_step IS NULL OR totp_last_used_step < $1)"#,
+        step as i64,
+        user_id,
+    )
+    .execute(database)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        tracing::warn!("rejecting a replayed TOTP code");
+        return Err(AuthError::InvalidCredentials);
+    }
+    Ok(())
+}
+
+async fn use_recovery_code(database: &PgPool, user_id: Uuid, code: &str) -> Result<(), AuthError> {
+    let result = sqlx::query!(
+        r#"UPDATE recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL"#,
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(database)
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AuthError::InvalidCredentials);
+    }
+    tracing::info!("a recovery code was used");
+    Ok(())
+}
+
+fn totp(secret: &str, username: &str) -> Result<TOTP, String> {
+    // The otpauth label uses `:` to separate the issuer from the account.
+    let account_name = username.replace(':', "_");
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| e.to_string())?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Return the time step `code` was generated for, tolerating one step of
+/// clock drift either way.
+fn verify_totp_code(
+    secret: &str,
+    username: &str,
+    code: &str,
+    unix_time: u64,
+) -> Result<Option<u64>, String> {
+    let totp = totp(secret, username)?;
+    let current_step = unix_time / TOTP_STEP_SECONDS;
+
+    let step = [
+        current_step.saturating_sub(1),
+        current_step,
+        current_step + 1,
+    ]
+    .into_iter()
+    .find(|step| totp.generate(step * TOTP_STEP_SECONDS) == code);
+    Ok(step)
+}
+
+fn unix_now() -> u64 {
+    OffsetDateTime::now_utc().unix_timestamp() as u64
+}
+
+fn generate_recovery_code() -> String {
+    let characters = generate_token(10).to_ascii_lowercase();
+    format!("{}-{}", &characters[..5], &characters[5..])
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hash_token(&code.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_some_eq};
+
+    use super::*;
+
+    // The SHA1 secret of the RFC 6238 test vectors, base32 encoded.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn codes_match_the_rfc_6238_test_vectors() {
+        assert_some_eq!(
+            verify_totp_code(RFC_SECRET, "user", "287082", 59).unwrap(),
+            1
+        );
+        assert_some_eq!(
+            verify_totp_code(RFC_SECRET, "user", "081804", 1111111109).unwrap(),
+            1111111109 / 30
+        );
+    }
+
+    #[test]
+    fn codes_from_adjacent_steps_are_accepted() {
+        let totp = totp(RFC_SECRET, "user").unwrap();
+        let now = 1111111109;
+
+        for offset in [-30, 30] {
+            let code = totp.generate((now + offset) as u64);
+            assert!(verify_totp_code(RFC_SECRET, "user", &code, now as u64)
+                .unwrap()
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn codes_from_distant_steps_are_rejected() {
+        let totp = totp(RFC_SECRET, "user").unwrap();
+        let now = 1111111109;
+        let code = totp.generate(now - 120);
+
+        assert_none!(verify_totp_code(RFC_SECRET, "user", &code, now).unwrap());
+    }
+
+    #[test]
+    fn the_otpauth_uri_names_the_issuer_and_account() {
+        let uri = totp(RFC_SECRET, "luigi").unwrap().get_url();
+
+        assert!(uri.starts_with("otpauth://totp/zero2prod:luigi?"));
+        assert!(uri.contains(&format!("secret={}", RFC_SECRET)));
+    }
+
+    #[test]
+    fn recovery_codes_are_hashed_case_insensitively() {
+        let code = generate_recovery_code();
+
+        assert_eq!(code.len(), 11);
+        assert_eq!(
+            hash_recovery_code(&code),
+            hash_recovery_code(&code.to_uppercase())
+        );
+    }
+}
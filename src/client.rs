@@ -0,0 +1,147 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// What we know about the client that sent the current request.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    /// The address of the client, as resolved by `identify_client`.
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub accept_language: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip_address = parts
+            .extensions
+            .get::<ClientIp>()
+            .map(|ClientIp(address)| address.to_string());
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        Ok(Self {
+            ip_address,
+            user_agent: header(header::USER_AGENT),
+            accept_language: header(header::ACCEPT_LANGUAGE),
+        })
+    }
+}
+
+/// The address of the client that sent the request, looking through the
+/// proxies we trust.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Resolve the address of the client and store it in the request extensions
+/// as a `ClientIp`.
+pub async fn identify_client(
+    State(trusted_proxies): State<Arc<Vec<IpAddr>>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    if let Some(peer) = peer {
+        let client = resolve_client_ip(peer, request.headers(), &trusted_proxies);
+        request.extensions_mut().insert(ClientIp(client));
+    }
+    next.run(request).await
+}
+
+/// Walk `X-Forwarded-For` back from the peer, for as long as the hops are
+/// trusted proxies. The first untrusted hop is the client: anything before
+/// it may have been made up by the client itself.
+fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    let forwarded_for = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    for hop in forwarded_for.rsplit(',') {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        let Ok(hop) = hop.trim().parse() else {
+            break;
+        };
+        client = hop;
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use axum::http::HeaderMap;
+
+    use super::resolve_client_ip;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn the_header_is_ignored_from_untrusted_peers() {
+        let headers = forwarded_for(&["203.0.113.7"]);
+
+        assert_eq!(
+            resolve_client_ip(ip("198.51.100.1"), &headers, &[]),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn the_client_is_the_first_untrusted_hop() {
+        let headers = forwarded_for(&["192.0.2.1, 203.0.113.7", "10.0.0.2"]);
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &trusted),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn a_malformed_hop_stops_the_walk() {
+        let headers = forwarded_for(&["203.0.113.7, not-an-address"]);
+        let trusted = [ip("10.0.0.1")];
+
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &trusted),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -1,22 +1,264 @@
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
 use secrecy::{ExposeSecret, SecretString};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 
-use crate::domain::SubscriberEmail;
+use crate::domain::{SubscriberEmail, ValidationError};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub email_client: EmailClientSettings,
+    pub admin: Option<AdminSettings>,
+    #[serde(default)]
+    pub email_templates: EmailTemplateSettings,
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct ApplicationSettings {
     pub host: String,
     pub port: u16,
-    pub base_url: String
+    pub base_url: String,
+    /// Salt for the hashes that keep erased addresses from subscribing again.
+    /// Changing it lifts the suppression of every address erased so far.
+    pub suppression_salt: SecretString,
+    #[serde(default)]
+    pub confirmation: ConfirmationSettings,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    pub signup_challenge: SignupChallengeSettings,
+    #[serde(default)]
+    pub email_policy: EmailPolicySettings,
+    #[serde(default)]
+    pub deliverability: DeliverabilitySettings,
+    #[serde(default)]
+    pub email_normalisation: EmailNormalisationSettings,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct ConfirmationSettings {
+    /// Flag confirmations submitted this soon after the confirmation email
+    /// was sent, as they are more likely to come from a link scanner than
+    /// from a person. Disabled when unset.
+    pub suspicious_within_seconds: Option<u64>,
+    /// How long confirmation links stay valid.
+    #[serde(default = "default_token_lifetime_hours")]
+    pub token_lifetime_hours: u64,
+    /// Where to send subscribers after following their confirmation link,
+    /// instead of showing them one of our own pages.
+    #[serde(default)]
+    pub redirects: ConfirmationRedirects,
+}
+
+impl Default for ConfirmationSettings {
+    fn default() -> Self {
+        Self {
+            suspicious_within_seconds: None,
+            token_lifetime_hours: default_token_lifetime_hours(),
+            redirects: ConfirmationRedirects::default(),
+        }
+    }
+}
+
+impl ConfirmationSettings {
+    pub fn token_lifetime(&self) -> time::Duration {
+        time::Duration::hours(self.token_lifetime_hours as i64)
+    }
+}
+
+fn default_token_lifetime_hours() -> u64 {
+    7 * 24
+}
+
+/// A redirect URL per outcome of a confirmation, each optional.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+pub struct ConfirmationRedirects {
+    pub confirmed: Option<String>,
+    pub already_confirmed: Option<String>,
+    pub invalid: Option<String>,
+    pub expired: Option<String>,
+}
+
+/// How addresses are compared to tell whether someone already subscribed.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct EmailNormalisationSettings {
+    /// Treat the aliases some providers deliver to the same mailbox, like
+    /// Gmail addresses with dots or a `+tag`, as the same address. Only
+    /// applies to addresses saved from then on.
+    pub fold_provider_aliases: bool,
+}
+
+impl EmailNormalisationSettings {
+    pub fn apply(&self, email: SubscriberEmail) -> SubscriberEmail {
+        if self.fold_provider_aliases {
+            email.fold_provider_aliases()
+        } else {
+            email
+        }
+    }
+}
+
+/// Whether the domains of new addresses are checked for mail servers, see
+/// `deliverability`.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DeliverabilitySettings {
+    pub mode: DeliverabilityMode,
+    /// How long the outcome of a lookup is reused for.
+    pub cache_ttl_seconds: u64,
+    /// How long to wait for DNS answers before letting the signup through.
+    pub timeout_millis: u64,
+}
+
+impl Default for DeliverabilitySettings {
+    fn default() -> Self {
+        Self {
+            mode: DeliverabilityMode::Off,
+            cache_ttl_seconds: 60 * 60,
+            timeout_millis: 2000,
+        }
+    }
+}
+
+impl DeliverabilitySettings {
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_seconds)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_millis)
+    }
+}
+
+/// What to do with signups from domains that cannot receive email.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliverabilityMode {
+    /// Do not look the domains up.
+    Off,
+    /// Accept the signup, logging a warning.
+    Soft,
+    /// Refuse the signup, suggesting a correction where we can.
+    Hard,
+}
+
+/// How signups from questionable addresses are handled, see `email_policy`.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct EmailPolicySettings {
+    /// Addresses at domains admins blocked.
+    pub blocked_domain: PolicyAction,
+    /// Addresses at disposable email services.
+    pub disposable_domain: PolicyAction,
+    /// Addresses of a function rather than a person, like `postmaster@`.
+    pub role_address: PolicyAction,
+    /// More disposable domains, one per line, on top of the bundled ones.
+    pub disposable_domains_file: Option<String>,
+}
+
+impl Default for EmailPolicySettings {
+    fn default() -> Self {
+        Self {
+            blocked_domain: PolicyAction::Reject,
+            disposable_domain: PolicyAction::Reject,
+            role_address: PolicyAction::Flag,
+            disposable_domains_file: None,
+        }
+    }
+}
+
+/// What to do with a signup an email policy check finds something about.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Refuse the signup.
+    Reject,
+    /// Accept the signup, but flag the subscriber for admins to review.
+    Flag,
+    /// Accept the signup as any other.
+    Allow,
+}
+
+/// The challenge signup forms must solve, see `signup_challenge`.
+///
+/// Signups are only challenged when a minimum fill time or a proof of work
+/// is configured.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SignupChallengeSettings {
+    /// Signs the challenge tokens handed out to signup forms.
+    pub secret: SecretString,
+    /// Drop signups submitted sooner than this after the form fetched its
+    /// challenge: people take longer than that to type their address.
+    pub min_fill_seconds: Option<u64>,
+    /// How many leading zero bits the hash of the challenge, the address and
+    /// the form's solution must have.
+    pub proof_of_work_bits: Option<u32>,
+    /// How long a challenge can be used for. Keep it short: a challenge can
+    /// be answered again until it expires.
+    #[serde(default = "default_challenge_max_age_seconds")]
+    pub max_age_seconds: u64,
+}
+
+impl SignupChallengeSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.min_fill_seconds.is_some() || self.proof_of_work_bits.is_some()
+    }
+}
+
+fn default_challenge_max_age_seconds() -> u64 {
+    10 * 60
+}
+
+/// Limits on how often the public endpoints can be called, see `rate_limit`.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    pub backend: RateLimitBackend,
+    /// Requests allowed from a single client address.
+    pub per_ip: RateLimit,
+    /// Requests allowed for a single email address submitted in a form,
+    /// whoever submits it.
+    pub per_email: RateLimit,
+    /// Proxies in front of the application. The client address is read from
+    /// the `X-Forwarded-For` header they set rather than from the connection.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            backend: RateLimitBackend::InProcess,
+            per_ip: RateLimit {
+                requests: 30,
+                window_seconds: 10 * 60,
+            },
+            per_email: RateLimit {
+                requests: 3,
+                window_seconds: 60 * 60,
+            },
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+/// Where request counters are kept.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitBackend {
+    /// In memory, each replica counting on its own.
+    InProcess,
+    /// In the database, shared by every replica.
+    Postgres,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub window_seconds: u64,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -50,6 +292,35 @@ impl DatabaseSettings {
     }
 }
 
+/// Where the templates of the emails we send are, see `email_templates`.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct EmailTemplateSettings {
+    pub directory: String,
+    /// Templates found here replace the bundled ones with the same path.
+    pub override_directory: Option<String>,
+    /// The locale of subscribers who did not pick one we have templates for.
+    pub default_locale: String,
+}
+
+impl Default for EmailTemplateSettings {
+    fn default() -> Self {
+        Self {
+            directory: "templates/email".into(),
+            override_directory: None,
+            default_locale: "en".into(),
+        }
+    }
+}
+
+/// Credentials of the owner account created on startup when no users exist yet.
+#[derive(serde::Deserialize, Clone)]
+pub struct AdminSettings {
+    pub username: String,
+    pub password: SecretString,
+    pub email: Option<String>,
+}
+
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
     pub base_url: String,
@@ -59,7 +330,7 @@ pub struct EmailClientSettings {
 }
 
 impl EmailClientSettings {
-    pub fn sender(&self) -> Result<SubscriberEmail, String> {
+    pub fn sender(&self) -> Result<SubscriberEmail, ValidationError> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
 
@@ -0,0 +1,112 @@
+//! Proof of how each subscriber opted in.
+
+use sqlx::PgExecutor;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::client::ClientInfo;
+
+/// A step of the double opt-in.
+pub enum ConsentEvent {
+    /// The subscription form was submitted.
+    Subscribe {
+        source: Option<String>,
+        consent_text_version: Option<String>,
+    },
+    /// The subscription was confirmed. The source and consent text are
+    /// those of the subscription to the same list being confirmed.
+    Confirm { suspiciously_fast: bool },
+}
+
+#[derive(serde::Serialize)]
+pub struct Consent {
+    event: String,
+    /// The slug of the list the subscriber opted in to.
+    list: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    source: Option<String>,
+    consent_text_version: Option<String>,
+    /// Whether the confirmation came so soon after the email was sent
+    /// that it might have been automated.
+    suspiciously_fast: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    recorded_at: OffsetDateTime,
+}
+
+#[tracing::instrument(name = "Record a consent", skip(executor, client, event))]
+pub async fn record_consent(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    client: &ClientInfo,
+    event: ConsentEvent,
+) -> Result<(), sqlx::Error> {
+    let query = match event {
+        ConsentEvent::Subscribe {
+            source,
+            consent_text_version,
+        } => sqlx::query!(
+            r#"INSERT INTO consents (consent_id, subscriber_id, list_id, event, ip_address,
+                user_agent, source, consent_text_version, recorded_at)
+            VALUES ($1, $2, $8, 'subscribe', $3, $4, $5, $6, $7)"#,
+            Uuid::new_v4(),
+            subscriber_id,
+            client.ip_address,
+            client.user_agent,
+            source,
+            consent_text_version,
+            OffsetDateTime::now_utc(),
+            list_id,
+        ),
+        ConsentEvent::Confirm { suspiciously_fast } => sqlx::query!(
+            r#"INSERT INTO consents (consent_id, subscriber_id, list_id, event, ip_address,
+                user_agent, source, consent_text_version, suspiciously_fast, recorded_at)
+            SELECT $1, $2, $7, 'confirm', $3, $4, subscribe.source,
+                subscribe.consent_text_version, $6, $5
+            FROM (SELECT 1) AS always
+            LEFT JOIN LATERAL (
+                SELECT source, consent_text_version FROM consents
+                WHERE subscriber_id = $2 AND list_id = $7 AND event = 'subscribe'
+                ORDER BY recorded_at DESC LIMIT 1
+            ) AS subscribe ON true"#,
+            Uuid::new_v4(),
+            subscriber_id,
+            client.ip_address,
+            client.user_agent,
+            OffsetDateTime::now_utc(),
+            suspiciously_fast,
+            list_id,
+        ),
+    };
+
+    query.execute(executor).await.map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+/// The consents recorded for a subscriber, oldest first.
+#[tracing::instrument(name = "List consents", skip(executor))]
+pub async fn list_consents(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Vec<Consent>, sqlx::Error> {
+    sqlx::query_as!(
+        Consent,
+        r#"SELECT c.event, l.slug AS list, c.ip_address, c.user_agent, c.source,
+            c.consent_text_version, c.suspiciously_fast, c.recorded_at
+        FROM consents c
+        JOIN lists l ON l.list_id = c.list_id
+        WHERE c.subscriber_id = $1
+        ORDER BY c.recorded_at"#,
+        subscriber_id,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
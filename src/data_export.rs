@@ -0,0 +1,261 @@
+//! Everything we hold about a subscriber, as they are entitled to under GDPR.
+
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{generate_token, hash_token},
+    consent::{list_consents, Consent},
+    domain::{SubscriberEmail, SubscriberName},
+    lists::{list_memberships, ListMembership},
+};
+
+const EXPORT_TOKEN_LIFETIME: Duration = Duration::hours(1);
+
+#[derive(serde::Serialize)]
+pub struct SubscriberArchive {
+    #[serde(with = "time::serde::rfc3339")]
+    generated_at: OffsetDateTime,
+    subscriber: SubscriberRecord,
+    lists: Vec<ListMembership>,
+    status_history: Vec<StatusChange>,
+    /// Only a prefix of each token is shown: the archive may travel by email
+    /// and must not be usable to act on the subscription.
+    subscription_tokens: Vec<String>,
+    email_events: Vec<EmailEvent>,
+    consents: Vec<Consent>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberRecord {
+    id: Uuid,
+    email: SubscriberEmail,
+    name: SubscriberName,
+    status: String,
+    locale: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    subscribed_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    confirmed_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    delivery_paused_at: Option<OffsetDateTime>,
+}
+
+#[derive(serde::Serialize)]
+struct StatusChange {
+    #[serde(with = "time::serde::rfc3339")]
+    occurred_at: OffsetDateTime,
+    action: String,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+}
+
+#[derive(serde::Serialize)]
+struct EmailEvent {
+    issue_title: String,
+    outcome: String,
+    #[serde(with = "time::serde::rfc3339")]
+    attempted_at: OffsetDateTime,
+}
+
+impl IntoResponse for SubscriberArchive {
+    fn into_response(self) -> Response {
+        (
+            [(
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"subscriber-{}.json\"",
+                    self.subscriber.id
+                ),
+            )],
+            Json(self),
+        )
+            .into_response()
+    }
+}
+
+/// Gather the data held about a subscriber, `None` if there is no such subscriber.
+#[tracing::instrument(name = "Compile a subscriber archive", skip(database))]
+pub async fn compile_subscriber_archive(
+    database: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberArchive>, sqlx::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberRecord,
+        r#"SELECT id, email AS "email: SubscriberEmail", name AS "name: SubscriberName",
+            status, locale, subscribed_at, confirmed_at, delivery_paused_at
+        FROM subscriptions WHERE id = $1"#,
+        subscriber_id,
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let Some(subscriber) = subscriber else {
+        return Ok(None);
+    };
+
+    let lists = list_memberships(database, subscriber_id).await?;
+
+    let status_history = sqlx::query_as!(
+        StatusChange,
+        r#"SELECT occurred_at, action, before, after FROM audit_log
+        WHERE target_type = 'subscriber' AND target_id = $1 AND after ? 'status'
+        ORDER BY id"#,
+        subscriber_id.to_string(),
+    )
+    .fetch_all(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let subscription_tokens = sqlx::query_scalar!(
+        r#"SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .fetch_all(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?
+    .iter()
+    .map(|token| redact(token))
+    .collect();
+
+    let email_events = sqlx::query_as!(
+        EmailEvent,
+        r#"SELECT i.title AS issue_title, d.outcome, d.attempted_at
+        FROM issue_deliveries d
+        JOIN newsletter_issues i USING (issue_id)
+        WHERE d.subscriber_id = $1
+        ORDER BY d.attempted_at"#,
+        subscriber_id,
+    )
+    .fetch_all(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let consents = list_consents(database, subscriber_id).await?;
+
+    Ok(Some(SubscriberArchive {
+        generated_at: OffsetDateTime::now_utc(),
+        subscriber,
+        lists,
+        status_history,
+        subscription_tokens,
+        email_events,
+        consents,
+    }))
+}
+
+/// A freshly issued data export token, with what is needed to email it.
+pub struct DataExportToken {
+    pub subscriber_id: Uuid,
+    pub locale: Option<String>,
+    pub token: String,
+}
+
+/// Issue a data export link token for the subscriber registered with the
+/// normalised email `email`.
+///
+/// Returns `None` if nobody subscribed with this address. Only a hash of the
+/// token is persisted.
+#[tracing::instrument(name = "Issue a data export token", skip(database, email))]
+pub async fn issue_data_export_token(
+    database: &PgPool,
+    email: &str,
+) -> Result<Option<DataExportToken>, sqlx::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT id, locale FROM subscriptions WHERE normalised_email = $1"#,
+        email
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let Some(subscriber) = subscriber else {
+        return Ok(None);
+    };
+
+    let token = generate_token(32);
+    let now = OffsetDateTime::now_utc();
+    sqlx::query!(
+        r#"INSERT INTO data_export_tokens (token_hash, subscriber_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)"#,
+        hash_token(&token),
+        subscriber.id,
+        now,
+        now + EXPORT_TOKEN_LIFETIME,
+    )
+    .execute(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(Some(DataExportToken {
+        subscriber_id: subscriber.id,
+        locale: subscriber.locale,
+        token,
+    }))
+}
+
+/// The subscriber a data export token was issued for, if it has not expired.
+///
+/// Tokens can be used until they expire, so that a mail scanner following
+/// the link does not lock the subscriber out.
+#[tracing::instrument(
+    name = "Get subscriber_id from data export token",
+    skip(database, token)
+)]
+pub async fn get_subscriber_id_from_export_token(
+    database: &PgPool,
+    token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT subscriber_id FROM data_export_tokens
+        WHERE token_hash = $1 AND expires_at > now()"#,
+        hash_token(token),
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+fn redact(token: &str) -> String {
+    let prefix: String = token.chars().take(4).collect();
+    format!("{}…", prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_tokens_only_keep_a_short_prefix() {
+        assert_eq!(redact("abcdefghijklmnopqrstuvwxy"), "abcd…");
+        assert_eq!(redact("ab"), "ab…");
+    }
+}
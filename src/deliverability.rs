@@ -0,0 +1,370 @@
+//! Whether new subscriber addresses can receive email at all.
+//!
+//! A typo in the domain, like `gmial.com`, gives an address that is well
+//! formed but bounces the confirmation email. The normalised domain is looked
+//! up for MX records, falling back on A/AAAA records as mail servers do,
+//! through a `DomainResolver` so that tests do not depend on the network.
+//! Outcomes are cached, and a lookup that fails for any other reason than the
+//! domain not existing lets the signup through.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use futures_util::future::BoxFuture;
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    system_conf::read_system_conf,
+    TokioAsyncResolver,
+};
+
+use crate::{
+    configuration::{DeliverabilityMode, DeliverabilitySettings},
+    domain::SubscriberEmail,
+};
+
+/// Cached outcomes are swept once the cache grows past this many domains.
+const MAX_CACHED_DOMAINS: usize = 10_000;
+
+/// Mailbox providers whose domains are commonly mistyped.
+const COMMON_PROVIDERS: &[&str] = &[
+    "aol.com",
+    "btinternet.com",
+    "comcast.net",
+    "free.fr",
+    "gmail.com",
+    "gmx.com",
+    "gmx.de",
+    "googlemail.com",
+    "hotmail.co.uk",
+    "hotmail.com",
+    "hotmail.fr",
+    "icloud.com",
+    "laposte.net",
+    "live.com",
+    "mail.com",
+    "me.com",
+    "msn.com",
+    "orange.fr",
+    "outlook.com",
+    "proton.me",
+    "protonmail.com",
+    "web.de",
+    "yahoo.co.uk",
+    "yahoo.com",
+    "yahoo.fr",
+    "yandex.ru",
+];
+
+/// Looks up whether a domain has somewhere to deliver email to.
+pub trait DomainResolver: Send + Sync {
+    /// `Ok(false)` when the domain does not exist or has no mail server,
+    /// `Err` when we could not find out.
+    fn accepts_mail(&self, domain: &str) -> BoxFuture<'_, Result<bool, ResolveError>>;
+}
+
+/// Resolves domains through DNS, with the system's configuration.
+pub struct DnsResolver(TokioAsyncResolver);
+
+impl DnsResolver {
+    pub fn new(settings: &DeliverabilitySettings) -> Self {
+        let (config, mut options) = read_system_conf().unwrap_or_else(|e| {
+            tracing::warn!("Failed to read the system DNS configuration: {:?}", e);
+            (ResolverConfig::default(), ResolverOpts::default())
+        });
+        options.timeout = settings.timeout();
+        options.attempts = 1;
+        Self(TokioAsyncResolver::tokio(config, options))
+    }
+}
+
+impl DomainResolver for DnsResolver {
+    fn accepts_mail(&self, domain: &str) -> BoxFuture<'_, Result<bool, ResolveError>> {
+        // Fully qualified, so that the search domains are not tried.
+        let domain = format!("{}.", domain.trim_end_matches('.'));
+        Box::pin(async move {
+            match self.0.mx_lookup(domain.as_str()).await {
+                // A single MX record for the root domain says the domain
+                // receives no email at all (RFC 7505).
+                Ok(mx) => Ok(mx.iter().any(|record| !record.exchange().is_root())),
+                Err(e) if is_no_records(&e) => match self.0.lookup_ip(domain.as_str()).await {
+                    Ok(ips) => Ok(ips.iter().next().is_some()),
+                    Err(e) if is_no_records(&e) => Ok(false),
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            }
+        })
+    }
+}
+
+fn is_no_records(error: &ResolveError) -> bool {
+    matches!(error.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+/// Knows which domains accept email from a fixed list, for tests.
+pub struct StaticResolver(HashSet<String>);
+
+impl StaticResolver {
+    pub fn new<'a>(domains: impl IntoIterator<Item = &'a str>) -> Self {
+        Self(domains.into_iter().map(str::to_lowercase).collect())
+    }
+}
+
+impl DomainResolver for StaticResolver {
+    fn accepts_mail(&self, domain: &str) -> BoxFuture<'_, Result<bool, ResolveError>> {
+        let accepts_mail = self.0.contains(domain);
+        Box::pin(async move { Ok(accepts_mail) })
+    }
+}
+
+/// The outcome of checking an address.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Deliverability {
+    Deliverable,
+    /// The domain cannot receive email. `suggestion` is the address the
+    /// subscriber may have meant, if the domain looks like a typo.
+    Undeliverable {
+        suggestion: Option<String>,
+    },
+    /// The check is off, or the lookup failed.
+    Unknown,
+}
+
+pub struct DeliverabilityCheck {
+    settings: DeliverabilitySettings,
+    resolver: Arc<dyn DomainResolver>,
+    cache: Mutex<HashMap<String, (Instant, bool)>>,
+}
+
+impl DeliverabilityCheck {
+    pub fn new(settings: DeliverabilitySettings, resolver: Arc<dyn DomainResolver>) -> Self {
+        Self {
+            settings,
+            resolver,
+            cache: Default::default(),
+        }
+    }
+
+    pub fn mode(&self) -> DeliverabilityMode {
+        self.settings.mode
+    }
+
+    #[tracing::instrument(name = "Check the deliverability of an email", skip_all)]
+    pub async fn check(&self, email: &SubscriberEmail) -> Deliverability {
+        if self.settings.mode == DeliverabilityMode::Off {
+            return Deliverability::Unknown;
+        }
+
+        let domain = email.normalised_domain().to_string();
+        let accepts_mail = match self.cached(&domain) {
+            Some(accepts_mail) => accepts_mail,
+            None => match self.resolver.accepts_mail(&domain).await {
+                Ok(accepts_mail) => {
+                    self.cache(domain.clone(), accepts_mail);
+                    accepts_mail
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to look up the mail servers of a domain: {:?}", e);
+                    return Deliverability::Unknown;
+                }
+            },
+        };
+
+        if accepts_mail {
+            Deliverability::Deliverable
+        } else {
+            Deliverability::Undeliverable {
+                suggestion: suggest_domain(&domain)
+                    .map(|domain| format!("{}@{}", email.local_part(), domain)),
+            }
+        }
+    }
+
+    fn cached(&self, domain: &str) -> Option<bool> {
+        let cache = self.cache.lock().unwrap();
+        let (looked_up_at, accepts_mail) = cache.get(domain)?;
+        (looked_up_at.elapsed() < self.settings.cache_ttl()).then_some(*accepts_mail)
+    }
+
+    fn cache(&self, domain: String, accepts_mail: bool) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_DOMAINS {
+            let ttl = self.settings.cache_ttl();
+            cache.retain(|_, (looked_up_at, _)| looked_up_at.elapsed() < ttl);
+        }
+        cache.insert(domain, (Instant::now(), accepts_mail));
+    }
+}
+
+/// The common provider `domain` is a likely typo of, if any.
+pub fn suggest_domain(domain: &str) -> Option<&'static str> {
+    if COMMON_PROVIDERS.contains(&domain) {
+        return None;
+    }
+    let max_distance = if domain.len() < 8 { 1 } else { 2 };
+    COMMON_PROVIDERS
+        .iter()
+        .map(|provider| (edit_distance(domain, provider), *provider))
+        .filter(|(distance, _)| (1..=max_distance).contains(distance))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, provider)| provider)
+}
+
+/// The number of insertions, deletions, substitutions and transpositions of
+/// adjacent characters that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use futures_util::future::BoxFuture;
+    use hickory_resolver::error::ResolveError;
+
+    use super::{
+        edit_distance, suggest_domain, Deliverability, DeliverabilityCheck, DomainResolver,
+        StaticResolver,
+    };
+    use crate::{
+        configuration::{DeliverabilityMode, DeliverabilitySettings},
+        domain::SubscriberEmail,
+    };
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.to_string()).unwrap()
+    }
+
+    fn settings(mode: DeliverabilityMode) -> DeliverabilitySettings {
+        DeliverabilitySettings {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    struct CountingResolver(AtomicUsize);
+
+    impl DomainResolver for CountingResolver {
+        fn accepts_mail(&self, _domain: &str) -> BoxFuture<'_, Result<bool, ResolveError>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async { Ok(true) })
+        }
+    }
+
+    #[test]
+    fn transpositions_count_as_a_single_edit() {
+        assert_eq!(edit_distance("gmial.com", "gmail.com"), 1);
+        assert_eq!(edit_distance("gmail.con", "gmail.com"), 1);
+        assert_eq!(edit_distance("hotmial.co", "hotmail.com"), 2);
+    }
+
+    #[test]
+    fn common_typos_get_a_suggestion() {
+        assert_eq!(suggest_domain("gmial.com"), Some("gmail.com"));
+        assert_eq!(suggest_domain("yaho.com"), Some("yahoo.com"));
+        assert_eq!(suggest_domain("outlok.com"), Some("outlook.com"));
+    }
+
+    #[test]
+    fn unrelated_domains_get_no_suggestion() {
+        assert_eq!(suggest_domain("gmail.com"), None);
+        assert_eq!(suggest_domain("example.org"), None);
+        assert_eq!(suggest_domain("mit.edu"), None);
+    }
+
+    #[tokio::test]
+    async fn undeliverable_typos_come_with_a_suggestion() {
+        let check = DeliverabilityCheck::new(
+            settings(DeliverabilityMode::Hard),
+            Arc::new(StaticResolver::new(["gmail.com"])),
+        );
+
+        assert_eq!(
+            check.check(&email("ursula@gmial.com")).await,
+            Deliverability::Undeliverable {
+                suggestion: Some("ursula@gmail.com".into())
+            }
+        );
+        assert_eq!(
+            check.check(&email("ursula@Gmail.com")).await,
+            Deliverability::Deliverable
+        );
+    }
+
+    #[tokio::test]
+    async fn lookups_are_cached() {
+        let resolver = Arc::new(CountingResolver(AtomicUsize::new(0)));
+        let check = DeliverabilityCheck::new(settings(DeliverabilityMode::Soft), resolver.clone());
+
+        check.check(&email("ursula@example.com")).await;
+        check.check(&email("le_guin@example.com")).await;
+
+        assert_eq!(resolver.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn internationalised_domains_are_looked_up_in_their_ascii_form() {
+        let resolver = Arc::new(CountingResolver(AtomicUsize::new(0)));
+        let check = DeliverabilityCheck::new(settings(DeliverabilityMode::Soft), resolver.clone());
+        let static_check = DeliverabilityCheck::new(
+            settings(DeliverabilityMode::Hard),
+            Arc::new(StaticResolver::new(["xn--bcher-kva.example", "gmail.com"])),
+        );
+
+        check.check(&email("ursula@Bücher.example")).await;
+        check.check(&email("le_guin@xn--bcher-kva.example")).await;
+
+        assert_eq!(resolver.0.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            static_check.check(&email("ursula@bücher.example")).await,
+            Deliverability::Deliverable
+        );
+        assert_eq!(
+            static_check.check(&email("ursula@ｇｍｉａｌ.com")).await,
+            Deliverability::Undeliverable {
+                suggestion: Some("ursula@gmail.com".into())
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn nothing_is_looked_up_when_the_check_is_off() {
+        let resolver = Arc::new(CountingResolver(AtomicUsize::new(0)));
+        let check = DeliverabilityCheck::new(settings(DeliverabilityMode::Off), resolver.clone());
+
+        assert_eq!(
+            check.check(&email("ursula@example.com")).await,
+            Deliverability::Unknown
+        );
+        assert_eq!(resolver.0.load(Ordering::Relaxed), 0);
+    }
+}
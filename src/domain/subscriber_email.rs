@@ -1,21 +1,157 @@
+use std::{fmt, str::FromStr};
+
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
 use validator::ValidateEmail;
 
+use crate::domain::ValidationError;
+
+/// Domains whose mailboxes ignore dots and anything after a `+` in the local
+/// part, and the domain they are all delivered to.
+const GMAIL_DOMAINS: &[&str] = &["gmail.com", "googlemail.com"];
+const GMAIL_CANONICAL_DOMAIN: &str = "gmail.com";
+
+/// An email address, as entered, and in the normalised form that tells
+/// whether two addresses reach the same mailbox.
 #[derive(Debug)]
-pub struct SubscriberEmail(String);
+pub struct SubscriberEmail {
+    address: String,
+    normalised: String,
+}
 
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<Self, String> {
-        if s.validate_email() {
-            Ok(Self(s))
-        } else {
-            Err(format!("{} is not a valid subscriber email", s))
+    pub fn parse(s: String) -> Result<Self, ValidationError> {
+        if !s.validate_email() {
+            return Err(ValidationError::InvalidEmail);
+        }
+        let normalised = normalise(&s).ok_or(ValidationError::InvalidEmail)?;
+        Ok(Self {
+            address: s,
+            normalised,
+        })
+    }
+}
+
+fn normalise(address: &str) -> Option<String> {
+    let (local_part, domain) = address.rsplit_once('@')?;
+    let domain = idna::domain_to_ascii(domain).ok()?;
+    Some(format!("{}@{}", local_part, domain))
+}
+
+impl SubscriberEmail {
+    /// What comes before the `@`.
+    pub fn local_part(&self) -> &str {
+        self.address
+            .rsplit_once('@')
+            .map_or("", |(local_part, _)| local_part)
+    }
+
+    /// What comes after the `@`.
+    pub fn domain(&self) -> &str {
+        self.address
+            .rsplit_once('@')
+            .map_or("", |(_, domain)| domain)
+    }
+
+    /// The address with its domain lowercased and in its ASCII form, which
+    /// subscribers are unique on.
+    pub fn normalised(&self) -> &str {
+        &self.normalised
+    }
+
+    /// The domain of the normalised form, which domain rules and lookups
+    /// should go by rather than the domain as entered.
+    pub fn normalised_domain(&self) -> &str {
+        self.normalised
+            .rsplit_once('@')
+            .map_or("", |(_, domain)| domain)
+    }
+
+    /// Also fold the aliases providers deliver to the same mailbox into the
+    /// normalised form, like `j.ohn+news@googlemail.com` for
+    /// `john@gmail.com`.
+    pub fn fold_provider_aliases(mut self) -> Self {
+        let Some((local_part, domain)) = self.normalised.rsplit_once('@') else {
+            return self;
+        };
+        if GMAIL_DOMAINS.contains(&domain) {
+            let local_part = local_part
+                .split_once('+')
+                .map_or(local_part, |(local_part, _)| local_part)
+                .replace('.', "")
+                .to_lowercase();
+            self.normalised = format!("{}@{}", local_part, GMAIL_CANONICAL_DOMAIN);
         }
+        self
     }
 }
 
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.address
+    }
+}
+
+impl fmt::Display for SubscriberEmail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.address)
+    }
+}
+
+impl FromStr for SubscriberEmail {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s.to_string())
+    }
+}
+
+impl serde::Serialize for SubscriberEmail {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.address)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SubscriberEmail {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Stored as the address as entered, the normalised form being computed
+/// again when it is read back.
+impl Type<Postgres> for SubscriberEmail {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for SubscriberEmail {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <&str as Encode<Postgres>>::encode_by_ref(&self.address.as_str(), buf)
+    }
+}
+
+/// Addresses are not validated again when read back: they were when they
+/// were saved, and tightening the rules since must not make rows unreadable.
+/// An address that cannot be normalised any more is its own normalised form.
+impl<'r> Decode<'r, Postgres> for SubscriberEmail {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let address = <String as Decode<Postgres>>::decode(value)?;
+        let normalised = normalise(&address).unwrap_or_else(|| address.clone());
+        Ok(Self {
+            address,
+            normalised,
+        })
     }
 }
 
@@ -45,6 +181,69 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn the_address_is_split_into_local_part_and_domain() {
+        let email = SubscriberEmail::parse("le.guin+news@mail.example.com".to_string()).unwrap();
+        assert_eq!(email.local_part(), "le.guin+news");
+        assert_eq!(email.domain(), "mail.example.com");
+    }
+
+    #[test]
+    fn the_domain_is_lowercased_in_the_normalised_form() {
+        let email = SubscriberEmail::parse("John@Example.COM".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "John@Example.COM");
+        assert_eq!(email.normalised(), "John@example.com");
+    }
+
+    #[test]
+    fn internationalised_domains_are_normalised_to_punycode() {
+        let unicode = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        let punycode = SubscriberEmail::parse("ursula@xn--bcher-kva.example".to_string()).unwrap();
+        assert_eq!(unicode.normalised(), "ursula@xn--bcher-kva.example");
+        assert_eq!(unicode.normalised(), punycode.normalised());
+    }
+
+    #[test]
+    fn the_normalised_domain_is_in_its_ascii_form() {
+        let fullwidth = SubscriberEmail::parse("ursula@ｍａｉｌｉｎａｔｏｒ.com".to_string()).unwrap();
+        let unicode = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        assert_eq!(fullwidth.domain(), "ｍａｉｌｉｎａｔｏｒ.com");
+        assert_eq!(fullwidth.normalised_domain(), "mailinator.com");
+        assert_eq!(unicode.normalised_domain(), "xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn gmail_aliases_are_folded_on_request() {
+        let email = SubscriberEmail::parse("J.ohn+news@GoogleMail.com".to_string()).unwrap();
+        assert_eq!(email.normalised(), "J.ohn+news@googlemail.com");
+        assert_eq!(email.fold_provider_aliases().normalised(), "john@gmail.com");
+    }
+
+    #[test]
+    fn other_providers_keep_their_dots_and_tags() {
+        let email = SubscriberEmail::parse("j.ohn+news@example.com".to_string())
+            .unwrap()
+            .fold_provider_aliases();
+        assert_eq!(email.normalised(), "j.ohn+news@example.com");
+    }
+
+    #[test]
+    fn emails_are_deserialised_through_parse() {
+        let email: SubscriberEmail = serde_json::from_str(r#""ursula@Example.com""#).unwrap();
+        assert_eq!(email.normalised(), "ursula@example.com");
+        assert_err!(serde_json::from_str::<SubscriberEmail>(r#""not-an-email""#));
+    }
+
+    #[test]
+    fn emails_are_serialised_and_displayed_as_entered() {
+        let email: SubscriberEmail = "ursula@Example.com".parse().unwrap();
+        assert_eq!(
+            serde_json::to_string(&email).unwrap(),
+            r#""ursula@Example.com""#
+        );
+        assert_eq!(email.to_string(), "ursula@Example.com");
+    }
+
     #[derive(Debug, Clone)]
     struct ValidEmailFixture(pub String);
 
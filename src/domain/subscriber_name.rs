@@ -1,23 +1,149 @@
+use std::{fmt, str::FromStr};
+
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::domain::ValidationError;
+
+const MAX_GRAPHEMES: usize = 256;
+
+const FORBIDDEN_CHARACTERS: [char; 9] = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
+
+/// Characters that change the direction of the text around them, which can
+/// make a name display as something else than what it is.
+const BIDI_CONTROLS: &[char] = &[
+    '\u{061C}', '\u{200E}', '\u{200F}', '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}',
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}',
+];
+
+/// Characters that take no space, beyond the joiners some scripts need.
+const INVISIBLE_CHARACTERS: &[char] = &['\u{200B}', '\u{2060}', '\u{FEFF}'];
+
+/// Top-level domains that show up in names that are actually adverts.
+const SPAM_TLDS: &[&str] = &[
+    "biz", "cc", "click", "club", "cn", "co", "com", "info", "io", "link", "me", "net", "online",
+    "org", "ru", "shop", "site", "top", "xyz",
+];
+
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
 impl SubscriberName {
-    pub fn parse(s: String) -> Result<Self, String> {
-        let is_empty_or_whitespace = s.trim().is_empty();
+    /// Parse a name, in its NFC form, without control characters and with
+    /// runs of whitespace collapsed into single spaces.
+    pub fn parse(s: String) -> Result<Self, ValidationError> {
+        let name = normalise(&s);
+        match rejection(&name) {
+            Some(error) => Err(error),
+            None => Ok(Self(name)),
+        }
+    }
+}
+
+fn normalise(s: &str) -> String {
+    let cleaned: String = s
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| {
+            !c.is_control() && !BIDI_CONTROLS.contains(c) && !INVISIBLE_CHARACTERS.contains(c)
+        })
+        .collect();
+    // Composed last, as removing a character can bring a combining mark
+    // next to the letter it goes with.
+    cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .nfc()
+        .collect()
+}
+
+fn rejection(name: &str) -> Option<ValidationError> {
+    if name.is_empty() {
+        return Some(ValidationError::EmptyName);
+    }
+    if name.graphemes(true).count() > MAX_GRAPHEMES {
+        return Some(ValidationError::NameTooLong {
+            max_graphemes: MAX_GRAPHEMES,
+        });
+    }
+    if let Some(c) = name.chars().find(|c| FORBIDDEN_CHARACTERS.contains(c)) {
+        return Some(ValidationError::ForbiddenCharacterInName(c));
+    }
+    if looks_like_url(name) {
+        return Some(ValidationError::NameLooksLikeUrl);
+    }
+    if name.split(' ').any(has_homoglyphs) {
+        return Some(ValidationError::HomoglyphsInName);
+    }
+    None
+}
+
+/// Whether the name contains a link, or a word that reads as a domain name
+/// like `cheap-pills.com`.
+fn looks_like_url(name: &str) -> bool {
+    let name = name.to_lowercase();
+    if name.contains("://") || name.contains("www.") || name.contains("http") {
+        return true;
+    }
+    name.split(' ').any(|word| {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        word.rsplit_once('.').is_some_and(|(host, tld)| {
+            SPAM_TLDS.contains(&tld)
+                && host.len() >= 2
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        })
+    })
+}
 
-        let is_too_long = s.graphemes(true).count() > 256;
+#[derive(PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    /// Letters styled to look like Latin ones, like 𝐛𝐨𝐥𝐝 or ｆｕｌｌｗｉｄｔｈ.
+    StyledLatin,
+    Other,
+}
 
-        let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-        let contains_forbidden_characters = s.chars().any(|g| forbidden_characters.contains(&g));
+fn script(c: char) -> Script {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Script::Latin,
+        '\u{0370}'..='\u{03FF}' => Script::Greek,
+        '\u{0400}'..='\u{052F}' => Script::Cyrillic,
+        '\u{FF21}'..='\u{FF3A}' | '\u{FF41}'..='\u{FF5A}' | '\u{1D400}'..='\u{1D7FF}' => {
+            Script::StyledLatin
+        }
+        _ => Script::Other,
+    }
+}
 
-        if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
-            Err(format!("{} is not a valid subscriber name.", s))
-        } else {
-            Ok(Self(s))
+/// Whether a word uses styled letters, or mixes Latin, Greek and Cyrillic
+/// letters, which look alike and are mixed to get past filters.
+fn has_homoglyphs(word: &str) -> bool {
+    let mut first = None;
+    for c in word.chars().filter(|c| c.is_alphabetic()) {
+        let script = script(c);
+        match script {
+            Script::StyledLatin => return true,
+            Script::Other => continue,
+            _ => {}
+        }
+        match &first {
+            None => first = Some(script),
+            Some(first) if *first != script => return true,
+            Some(_) => {}
         }
     }
+    false
 }
 
 impl AsRef<str> for SubscriberName {
@@ -26,9 +152,67 @@ impl AsRef<str> for SubscriberName {
     }
 }
 
+impl fmt::Display for SubscriberName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for SubscriberName {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s.to_string())
+    }
+}
+
+impl serde::Serialize for SubscriberName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SubscriberName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Type<Postgres> for SubscriberName {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for SubscriberName {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <&str as Encode<Postgres>>::encode_by_ref(&self.0.as_str(), buf)
+    }
+}
+
+/// Names are not validated again when read back: they were when they were
+/// saved, and tightening the rules since must not make rows unreadable.
+impl<'r> Decode<'r, Postgres> for SubscriberName {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        <String as Decode<Postgres>>::decode(value).map(Self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use claims::{assert_err, assert_ok};
+    use claims::{assert_err, assert_err_eq, assert_ok};
+    use fake::{
+        faker::name::raw::Name,
+        locales::{AR_SA, DE_DE, EN, FR_FR, JA_JP, ZH_CN},
+        Fake,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use unicode_normalization::UnicodeNormalization;
 
     use super::*;
 
@@ -68,4 +252,129 @@ mod tests {
         let name = "Luigi Mario".to_string();
         assert_ok!(SubscriberName::parse(name));
     }
+
+    #[test]
+    fn rejections_say_why() {
+        let cases = [
+            ("\u{202E}\u{200B} ", ValidationError::EmptyName),
+            (
+                "Luigi <Mario>",
+                ValidationError::ForbiddenCharacterInName('<'),
+            ),
+            ("Visit http-cheap pills", ValidationError::NameLooksLikeUrl),
+            ("Luigi www.mario", ValidationError::NameLooksLikeUrl),
+            ("cheap-pills.com", ValidationError::NameLooksLikeUrl),
+            ("Luigi Mаrio", ValidationError::HomoglyphsInName),
+            ("𝐋𝐮𝐢𝐠𝐢", ValidationError::HomoglyphsInName),
+        ];
+        for (name, expected) in cases {
+            assert_err_eq!(
+                SubscriberName::parse(name.to_string()),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn names_with_dots_or_several_alphabets_are_not_taken_for_spam() {
+        for name in [
+            "J.R.R. Tolkien",
+            "Ursula K. Le Guin",
+            "Ivan Иван",
+            "Ζωή Mario",
+        ] {
+            assert_ok!(SubscriberName::parse(name.to_string()));
+        }
+    }
+
+    #[test]
+    fn control_and_bidi_characters_are_stripped_and_whitespace_collapsed() {
+        let name =
+            SubscriberName::parse(" Luigi\t\u{202E}\u{0007}  \n Mario ".to_string()).unwrap();
+        assert_eq!(name.as_ref(), "Luigi Mario");
+    }
+
+    #[test]
+    fn names_are_normalised_to_nfc() {
+        let name = SubscriberName::parse("Zoe\u{0308}".to_string()).unwrap();
+        assert_eq!(name.as_ref(), "Zoë");
+    }
+
+    #[test]
+    fn names_are_deserialised_through_parse() {
+        let name: SubscriberName = serde_json::from_str(r#"" Luigi   Mario ""#).unwrap();
+        assert_eq!(name.as_ref(), "Luigi Mario");
+        assert_err!(serde_json::from_str::<SubscriberName>(r#""<b>Luigi</b>""#));
+    }
+
+    #[test]
+    fn names_are_serialised_and_displayed_as_parsed() {
+        let name: SubscriberName = "Luigi\tMario".parse().unwrap();
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""Luigi Mario""#);
+        assert_eq!(name.to_string(), "Luigi Mario");
+    }
+
+    #[derive(Debug, Clone)]
+    struct ValidNameFixture(pub String);
+
+    impl quickcheck::Arbitrary for ValidNameFixture {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let mut rng = StdRng::seed_from_u64(u64::arbitrary(g));
+            let name = match u8::arbitrary(g) % 6 {
+                0 => Name(EN).fake_with_rng(&mut rng),
+                1 => Name(FR_FR).fake_with_rng(&mut rng),
+                2 => Name(DE_DE).fake_with_rng(&mut rng),
+                3 => Name(JA_JP).fake_with_rng(&mut rng),
+                4 => Name(ZH_CN).fake_with_rng(&mut rng),
+                _ => Name(AR_SA).fake_with_rng(&mut rng),
+            };
+
+            Self(name)
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn valid_names_are_parsed_successfully(valid_name: ValidNameFixture) -> bool {
+        SubscriberName::parse(valid_name.0).is_ok()
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn decomposed_names_parse_to_the_same_name(valid_name: ValidNameFixture) -> bool {
+        let composed = SubscriberName::parse(valid_name.0.clone()).unwrap();
+        let decomposed = SubscriberName::parse(valid_name.0.nfd().collect()).unwrap();
+        composed.as_ref() == decomposed.as_ref()
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn names_with_a_link_are_rejected(valid_name: ValidNameFixture) -> bool {
+        SubscriberName::parse(format!("{} https://example.com", valid_name.0)).is_err()
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn parsed_names_are_clean(s: String) -> bool {
+        match SubscriberName::parse(s) {
+            Ok(name) => {
+                let name = name.as_ref();
+                name == name.trim()
+                    && !name.contains("  ")
+                    && !name.chars().any(|c| {
+                        c.is_control()
+                            || (c.is_whitespace() && c != ' ')
+                            || BIDI_CONTROLS.contains(&c)
+                    })
+            }
+            Err(_) => true,
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn parsing_a_parsed_name_gives_it_back(s: String) -> bool {
+        match SubscriberName::parse(s) {
+            Ok(name) => SubscriberName::parse(name.as_ref().to_string())
+                .is_ok_and(|again| again.as_ref() == name.as_ref()),
+            Err(_) => true,
+        }
+    }
 }
@@ -0,0 +1,129 @@
+use std::fmt;
+
+use serde::ser::SerializeStruct;
+
+/// Why a value submitted for a subscriber field was refused.
+///
+/// API clients get it as `{"field": ..., "code": ..., "message": ...}`, the
+/// code being stable and the message meant for people.
+///
+/// Addresses can also be refused by the email policy, or because their
+/// domain cannot receive email, with the address the subscriber may have
+/// meant as `suggestion`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    InvalidEmail,
+    EmailNotAccepted,
+    UndeliverableEmail(Option<String>),
+    EmptyName,
+    NameTooLong { max_graphemes: usize },
+    ForbiddenCharacterInName(char),
+    NameLooksLikeUrl,
+    HomoglyphsInName,
+}
+
+impl ValidationError {
+    /// The field the value was submitted for.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidEmail | Self::EmailNotAccepted | Self::UndeliverableEmail(_) => "email",
+            Self::EmptyName
+            | Self::NameTooLong { .. }
+            | Self::ForbiddenCharacterInName(_)
+            | Self::NameLooksLikeUrl
+            | Self::HomoglyphsInName => "name",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidEmail => "invalid",
+            Self::EmailNotAccepted => "not_accepted",
+            Self::UndeliverableEmail(_) => "undeliverable",
+            Self::EmptyName => "empty",
+            Self::NameTooLong { .. } => "too_long",
+            Self::ForbiddenCharacterInName(_) => "forbidden_character",
+            Self::NameLooksLikeUrl => "looks_like_url",
+            Self::HomoglyphsInName => "homoglyphs",
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEmail => write!(f, "This is not a valid email address."),
+            Self::EmailNotAccepted => {
+                write!(f, "Signups from this email address are not accepted.")
+            }
+            Self::UndeliverableEmail(None) => {
+                write!(f, "The domain of this email address cannot receive email.")
+            }
+            Self::UndeliverableEmail(Some(suggestion)) => write!(
+                f,
+                "The domain of this email address cannot receive email. Did you mean {}?",
+                suggestion
+            ),
+            Self::EmptyName => write!(f, "The name is empty."),
+            Self::NameTooLong { max_graphemes } => {
+                write!(f, "The name is longer than {} characters.", max_graphemes)
+            }
+            Self::ForbiddenCharacterInName(c) => write!(f, "The name cannot contain {:?}.", c),
+            Self::NameLooksLikeUrl => write!(f, "The name looks like a web address."),
+            Self::HomoglyphsInName => write!(
+                f,
+                "The name mixes look-alike letters from different alphabets."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl serde::Serialize for ValidationError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("ValidationError", 4)?;
+        error.serialize_field("field", self.field())?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        match self {
+            Self::UndeliverableEmail(suggestion) => {
+                error.serialize_field("suggestion", suggestion)?
+            }
+            _ => error.skip_field("suggestion")?,
+        }
+        error.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValidationError;
+
+    #[test]
+    fn errors_are_serialised_with_their_field_code_and_message() {
+        let error = serde_json::to_value(ValidationError::ForbiddenCharacterInName('<')).unwrap();
+        assert_eq!(
+            error,
+            serde_json::json!({
+                "field": "name",
+                "code": "forbidden_character",
+                "message": "The name cannot contain '<'.",
+            })
+        );
+    }
+
+    #[test]
+    fn undeliverable_addresses_come_with_their_suggestion() {
+        let error = serde_json::to_value(ValidationError::UndeliverableEmail(Some(
+            "ursula@gmail.com".into(),
+        )))
+        .unwrap();
+        assert_eq!(error["code"], "undeliverable");
+        assert_eq!(error["suggestion"], "ursula@gmail.com");
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .contains("ursula@gmail.com"));
+    }
+}
@@ -0,0 +1,306 @@
+//! Which email addresses we take signups from.
+//!
+//! `SubscriberEmail::parse` only checks that an address is well formed. The
+//! policy looks at who is behind it: disposable email services, from a list
+//! bundled with the application, domains admins blocked or allowed in the
+//! `email_domain_rules` table, and role addresses like `postmaster@`, which
+//! belong to a function rather than a person. What to do about each is up to
+//! the configuration.
+
+use std::collections::HashSet;
+
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{
+    configuration::{EmailPolicySettings, PolicyAction},
+    domain::SubscriberEmail,
+};
+
+const BUNDLED_DISPOSABLE_DOMAINS: &str = include_str!("disposable_domains.txt");
+
+/// Local parts of addresses that reach a function rather than a person.
+const ROLE_LOCAL_PARTS: &[&str] = &[
+    "abuse",
+    "admin",
+    "administrator",
+    "do-not-reply",
+    "donotreply",
+    "hostmaster",
+    "mailer-daemon",
+    "no-reply",
+    "noreply",
+    "postmaster",
+    "root",
+    "security",
+    "webmaster",
+];
+
+/// Something the policy found about an address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyFinding {
+    BlockedDomain,
+    DisposableDomain,
+    RoleAddress,
+}
+
+impl PolicyFinding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BlockedDomain => "blocked_domain",
+            Self::DisposableDomain => "disposable_domain",
+            Self::RoleAddress => "role_address",
+        }
+    }
+}
+
+/// What to do with a signup.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Flag(PolicyFinding),
+    Reject(PolicyFinding),
+}
+
+/// A rule admins set for a domain and its subdomains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DomainRule {
+    Block,
+    Allow,
+}
+
+impl DomainRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Allow => "allow",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "block" => Some(Self::Block),
+            "allow" => Some(Self::Allow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EmailPolicy {
+    settings: EmailPolicySettings,
+    disposable_domains: HashSet<String>,
+}
+
+impl EmailPolicy {
+    /// The policy, with the bundled disposable domains and those of the
+    /// configured file.
+    pub fn load(settings: &EmailPolicySettings) -> std::io::Result<Self> {
+        let mut disposable_domains = domain_list(BUNDLED_DISPOSABLE_DOMAINS);
+        if let Some(path) = &settings.disposable_domains_file {
+            disposable_domains.extend(domain_list(&std::fs::read_to_string(path)?));
+        }
+        Ok(Self {
+            settings: settings.clone(),
+            disposable_domains,
+        })
+    }
+
+    /// Decide what to do with a signup from `email`.
+    #[tracing::instrument(name = "Check the email policy", skip_all)]
+    pub async fn check(
+        &self,
+        executor: impl PgExecutor<'_>,
+        email: &SubscriberEmail,
+    ) -> Result<Verdict, sqlx::Error> {
+        let domain = email.normalised_domain();
+        let rule = get_domain_rule(executor, domain).await?;
+        Ok(self.verdict(&self.findings(email, domain, rule)))
+    }
+
+    fn findings(
+        &self,
+        email: &SubscriberEmail,
+        domain: &str,
+        rule: Option<DomainRule>,
+    ) -> Vec<PolicyFinding> {
+        let mut findings = Vec::new();
+        match rule {
+            Some(DomainRule::Allow) => {}
+            Some(DomainRule::Block) => findings.push(PolicyFinding::BlockedDomain),
+            None => {
+                let disposable =
+                    parent_domains(domain).any(|domain| self.disposable_domains.contains(domain));
+                if disposable {
+                    findings.push(PolicyFinding::DisposableDomain);
+                }
+            }
+        }
+        if is_role_address(email.local_part()) {
+            findings.push(PolicyFinding::RoleAddress);
+        }
+        findings
+    }
+
+    /// The strictest action configured for any of the `findings`.
+    fn verdict(&self, findings: &[PolicyFinding]) -> Verdict {
+        let action = |finding: &PolicyFinding| match finding {
+            PolicyFinding::BlockedDomain => self.settings.blocked_domain,
+            PolicyFinding::DisposableDomain => self.settings.disposable_domain,
+            PolicyFinding::RoleAddress => self.settings.role_address,
+        };
+        if let Some(finding) = findings.iter().find(|f| action(f) == PolicyAction::Reject) {
+            return Verdict::Reject(*finding);
+        }
+        if let Some(finding) = findings.iter().find(|f| action(f) == PolicyAction::Flag) {
+            return Verdict::Flag(*finding);
+        }
+        Verdict::Accept
+    }
+}
+
+/// Whether the address reaches a function rather than a person, ignoring
+/// any `+tag`.
+pub fn is_role_address(local_part: &str) -> bool {
+    let local_part = local_part.to_lowercase();
+    let local_part = local_part
+        .split_once('+')
+        .map_or(local_part.as_str(), |(local_part, _)| local_part);
+    ROLE_LOCAL_PARTS.contains(&local_part)
+}
+
+/// `domain` and the domains it is a subdomain of, most specific first.
+fn parent_domains(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |domain| {
+        domain.split_once('.').map(|(_, parent)| parent)
+    })
+    .filter(|domain| !domain.is_empty())
+}
+
+fn domain_list(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The rule set for the most specific of `domain` and its parent domains.
+async fn get_domain_rule(
+    executor: impl PgExecutor<'_>,
+    domain: &str,
+) -> Result<Option<DomainRule>, sqlx::Error> {
+    let domains: Vec<String> = parent_domains(domain).map(str::to_owned).collect();
+    let rule = sqlx::query_scalar!(
+        r#"SELECT rule FROM email_domain_rules
+        WHERE domain = ANY($1)
+        ORDER BY length(domain) DESC
+        LIMIT 1"#,
+        &domains,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(rule.as_deref().and_then(DomainRule::parse))
+}
+
+/// Record why a subscriber was flagged, for admins to review.
+#[tracing::instrument(name = "Flag a subscriber", skip(executor))]
+pub async fn flag_subscriber(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    finding: PolicyFinding,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET flagged_reason = $1 WHERE id = $2"#,
+        finding.as_str(),
+        subscriber_id,
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_role_address, EmailPolicy, PolicyFinding, Verdict};
+    use crate::{
+        configuration::{EmailPolicySettings, PolicyAction},
+        domain::SubscriberEmail,
+    };
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.to_string()).unwrap()
+    }
+
+    fn verdict(policy: &EmailPolicy, address: &str) -> Verdict {
+        let email = email(address);
+        policy.verdict(&policy.findings(&email, email.normalised_domain(), None))
+    }
+
+    #[test]
+    fn disposable_domains_and_their_subdomains_are_found() {
+        let policy = EmailPolicy::load(&EmailPolicySettings::default()).unwrap();
+
+        assert_eq!(
+            verdict(&policy, "ursula@Mailinator.com"),
+            Verdict::Reject(PolicyFinding::DisposableDomain)
+        );
+        assert_eq!(
+            verdict(&policy, "ursula@eu.mailinator.com"),
+            Verdict::Reject(PolicyFinding::DisposableDomain)
+        );
+        assert_eq!(
+            verdict(&policy, "ursula@ｍａｉｌｉｎａｔｏｒ.com"),
+            Verdict::Reject(PolicyFinding::DisposableDomain)
+        );
+        assert_eq!(verdict(&policy, "ursula@gmail.com"), Verdict::Accept);
+    }
+
+    #[test]
+    fn role_addresses_are_found_with_or_without_a_tag() {
+        assert!(is_role_address("postmaster"));
+        assert!(is_role_address("NoReply+newsletter"));
+        assert!(!is_role_address("ursula"));
+    }
+
+    #[test]
+    fn the_strictest_action_wins() {
+        let policy = EmailPolicy::load(&EmailPolicySettings {
+            disposable_domain: PolicyAction::Flag,
+            role_address: PolicyAction::Reject,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            verdict(&policy, "noreply@yopmail.com"),
+            Verdict::Reject(PolicyFinding::RoleAddress)
+        );
+        assert_eq!(
+            verdict(&policy, "ursula@yopmail.com"),
+            Verdict::Flag(PolicyFinding::DisposableDomain)
+        );
+    }
+
+    #[test]
+    fn allowed_findings_are_ignored() {
+        let policy = EmailPolicy::load(&EmailPolicySettings {
+            disposable_domain: PolicyAction::Allow,
+            role_address: PolicyAction::Allow,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(verdict(&policy, "postmaster@yopmail.com"), Verdict::Accept);
+    }
+}
@@ -0,0 +1,470 @@
+//! The emails we send, rendered from templates on disk.
+//!
+//! Templates are grouped by locale, then by email: each email has a
+//! directory under `<locale>/`, holding its `subject.txt`, `body.html` and
+//! `body.txt`. Every email must exist in the default locale, other locales
+//! may translate only some of them. Any file can be replaced per
+//! environment by a file at the same path under the override directory.
+//!
+//! Templates are checked on startup, so that a broken template stops the
+//! application rather than the emails.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use minijinja::{
+    context, escape_formatter, AutoEscape, Environment, ErrorKind, Output, State,
+    UndefinedBehavior, Value,
+};
+
+use crate::configuration::EmailTemplateSettings;
+
+const PARTS: [&str; 3] = ["subject.txt", "body.html", "body.txt"];
+
+#[derive(Clone, Copy, Debug)]
+pub enum EmailTemplate {
+    /// Variables: `name`, `confirmation_link`.
+    Confirmation,
+    /// Variables: `reset_link`.
+    PasswordReset,
+    /// Variables: `export_link`.
+    DataExport,
+}
+
+impl EmailTemplate {
+    const ALL: [Self; 3] = [Self::Confirmation, Self::PasswordReset, Self::DataExport];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Confirmation => "confirmation",
+            Self::PasswordReset => "password_reset",
+            Self::DataExport => "data_export",
+        }
+    }
+
+    /// A value for every variable the template gets, to check it on startup.
+    fn sample_context(&self) -> Value {
+        let link = "https://example.com/link?token=sample";
+        match self {
+            Self::Confirmation => context! { name => "Ursula", confirmation_link => link },
+            Self::PasswordReset => context! { reset_link => link },
+            Self::DataExport => context! { export_link => link },
+        }
+    }
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Read(PathBuf, std::io::Error),
+    /// A locale translates some parts of an email but not all of them.
+    Incomplete {
+        locale: String,
+        template: String,
+    },
+    Invalid(minijinja::Error),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Self::Incomplete { locale, template } => write!(
+                f,
+                "the {} email is only partially translated to {}",
+                template, locale
+            ),
+            Self::Invalid(e) => write!(f, "invalid email template: {:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<minijinja::Error> for TemplateError {
+    fn from(e: minijinja::Error) -> Self {
+        Self::Invalid(e)
+    }
+}
+
+/// Why an email could not be sent.
+#[derive(Debug)]
+pub enum EmailError {
+    Template(minijinja::Error),
+    Send(reqwest::Error),
+}
+
+impl From<minijinja::Error> for EmailError {
+    fn from(e: minijinja::Error) -> Self {
+        Self::Template(e)
+    }
+}
+
+impl From<reqwest::Error> for EmailError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Send(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct EmailTemplates {
+    environment: Environment<'static>,
+    default_locale: String,
+    locales: BTreeSet<String>,
+}
+
+impl EmailTemplates {
+    /// Load and check every template.
+    pub fn load(settings: &EmailTemplateSettings) -> Result<Self, TemplateError> {
+        let mut environment = Environment::new();
+        // A misspelled variable must fail the check rather than render as nothing.
+        environment.set_undefined_behavior(UndefinedBehavior::Strict);
+        environment.set_formatter(escape_html);
+
+        let mut locales = list_locales(Path::new(&settings.directory))?;
+        if let Some(directory) = &settings.override_directory {
+            locales.extend(list_locales(Path::new(directory))?);
+        }
+        locales.insert(settings.default_locale.clone());
+
+        for locale in &locales {
+            for template in EmailTemplate::ALL {
+                let mut sources = Vec::with_capacity(PARTS.len());
+                for part in PARTS {
+                    let name = template_name(locale, template, part);
+                    if let Some(source) = read_template(settings, &name)? {
+                        sources.push((name, source));
+                    }
+                }
+
+                match sources.len() {
+                    // The default locale is checked below, when rendering.
+                    0 if locale != &settings.default_locale => continue,
+                    n if n == PARTS.len() => {}
+                    0 => {}
+                    _ => {
+                        return Err(TemplateError::Incomplete {
+                            locale: locale.clone(),
+                            template: template.name().into(),
+                        })
+                    }
+                }
+                for (name, source) in sources {
+                    environment.add_template_owned(name, source)?;
+                }
+            }
+        }
+
+        let templates = Self {
+            environment,
+            default_locale: settings.default_locale.clone(),
+            locales,
+        };
+        for locale in &templates.locales {
+            for template in EmailTemplate::ALL {
+                templates.render(template, Some(locale), template.sample_context())?;
+            }
+        }
+        Ok(templates)
+    }
+
+    /// The supported locale closest to `tag`, if any: `fr-CA` falls back to `fr`.
+    pub fn supported_locale(&self, tag: &str) -> Option<String> {
+        let tag = tag.trim().to_lowercase();
+        if self.locales.contains(&tag) {
+            return Some(tag);
+        }
+        let (language, _) = tag.split_once('-')?;
+        self.locales.get(language).cloned()
+    }
+
+    /// The locale to write to a new subscriber in: the one they picked,
+    /// else the best match for their browser's languages.
+    pub fn negotiate_locale(
+        &self,
+        requested: Option<&str>,
+        accept_language: Option<&str>,
+    ) -> Option<String> {
+        requested
+            .and_then(|tag| self.supported_locale(tag))
+            .or_else(|| {
+                parse_accept_language(accept_language?)
+                    .into_iter()
+                    .find_map(|tag| self.supported_locale(&tag))
+            })
+    }
+
+    /// Render `template` in `locale`, or in the default locale if it is not
+    /// translated to `locale`. `.html` parts escape the variables they contain.
+    pub fn render(
+        &self,
+        template: EmailTemplate,
+        locale: Option<&str>,
+        context: Value,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        let locale = locale
+            .filter(|locale| {
+                self.environment
+                    .get_template(&template_name(locale, template, PARTS[0]))
+                    .is_ok()
+            })
+            .unwrap_or(&self.default_locale);
+
+        let render = |part: &str| {
+            self.environment
+                .get_template(&template_name(locale, template, part))
+                .map_err(|e| match e.kind() {
+                    ErrorKind::TemplateNotFound => minijinja::Error::new(
+                        ErrorKind::TemplateNotFound,
+                        format!("{} is missing", template_name(locale, template, part)),
+                    ),
+                    _ => e,
+                })?
+                .render(&context)
+        };
+
+        Ok(RenderedEmail {
+            subject: render("subject.txt")?.trim().to_string(),
+            html: render("body.html")?,
+            text: render("body.txt")?,
+        })
+    }
+}
+
+fn template_name(locale: &str, template: EmailTemplate, part: &str) -> String {
+    format!("{}/{}/{}", locale, template.name(), part)
+}
+
+/// The language tags of an `Accept-Language` header, most preferred first.
+fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut tags: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parameters = entry.split(';');
+            let tag = parameters.next()?.trim();
+            let quality = parameters
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .collect();
+    // Stable, so that equally preferred tags keep their order.
+    tags.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+/// The default escaping, except that `/` is left alone in HTML. Escaping it
+/// is valid, but garbles the links for anything that reads the source
+/// rather than rendering it.
+fn escape_html(out: &mut Output, state: &State, value: &Value) -> Result<(), minijinja::Error> {
+    match value.as_str() {
+        Some(s) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => {
+            let escaped = s
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&#x27;");
+            out.write_str(&escaped)?;
+            Ok(())
+        }
+        _ => escape_formatter(out, state, value),
+    }
+}
+
+/// The locales `directory` has templates for, one subdirectory each.
+fn list_locales(directory: &Path) -> Result<BTreeSet<String>, TemplateError> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        // A missing directory is reported with the first missing template.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(e) => return Err(TemplateError::Read(directory.into(), e)),
+    };
+
+    let mut locales = BTreeSet::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| TemplateError::Read(directory.into(), e))?;
+        if entry.path().is_dir() {
+            if let Some(locale) = entry.file_name().to_str() {
+                locales.insert(locale.to_lowercase());
+            }
+        }
+    }
+    Ok(locales)
+}
+
+/// The source of template `name`, `None` if there is no such file.
+fn read_template(
+    settings: &EmailTemplateSettings,
+    name: &str,
+) -> Result<Option<String>, TemplateError> {
+    let path = settings
+        .override_directory
+        .as_deref()
+        .map(|directory| Path::new(directory).join(name))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| Path::new(&settings.directory).join(name));
+
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(TemplateError::Read(path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn bundled() -> EmailTemplateSettings {
+        EmailTemplateSettings::default()
+    }
+
+    /// A fresh directory holding `files`, as `(name, contents)`.
+    fn directory_with(files: &[(&str, &str)]) -> String {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&directory).unwrap();
+        for (name, contents) in files {
+            let path = directory.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        directory.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn the_bundled_templates_are_valid() {
+        assert_ok!(EmailTemplates::load(&bundled()));
+    }
+
+    #[test]
+    fn variables_are_escaped_in_html_only() {
+        let templates = EmailTemplates::load(&bundled()).unwrap();
+
+        let email = templates
+            .render(
+                EmailTemplate::Confirmation,
+                None,
+                context! { name => "<Ursula>", confirmation_link => "https://example.com" },
+            )
+            .unwrap();
+
+        assert!(email.html.contains("&lt;Ursula&gt;"));
+        assert!(email.html.contains(r#"href="https://example.com""#));
+        assert!(email.text.contains("<Ursula>"));
+        assert_eq!(email.subject, "Welcome!");
+    }
+
+    #[test]
+    fn overrides_replace_single_files() {
+        let settings = EmailTemplateSettings {
+            override_directory: Some(directory_with(&[(
+                "en/confirmation/subject.txt",
+                "Confirm your subscription, {{ name }}",
+            )])),
+            ..bundled()
+        };
+        let templates = EmailTemplates::load(&settings).unwrap();
+
+        let email = templates
+            .render(
+                EmailTemplate::Confirmation,
+                None,
+                EmailTemplate::Confirmation.sample_context(),
+            )
+            .unwrap();
+
+        assert_eq!(email.subject, "Confirm your subscription, Ursula");
+        assert!(email.text.contains("Welcome to our newsletter"));
+    }
+
+    #[test]
+    fn templates_using_unknown_variables_are_rejected() {
+        let settings = EmailTemplateSettings {
+            override_directory: Some(directory_with(&[(
+                "en/confirmation/body.txt",
+                "Visit {{ confirmaton_link }}",
+            )])),
+            ..bundled()
+        };
+
+        assert_err!(EmailTemplates::load(&settings));
+    }
+
+    #[test]
+    fn missing_templates_are_rejected() {
+        let settings = EmailTemplateSettings {
+            directory: directory_with(&[]),
+            ..bundled()
+        };
+
+        assert_err!(EmailTemplates::load(&settings));
+    }
+
+    #[test]
+    fn partial_translations_of_an_email_are_rejected() {
+        let settings = EmailTemplateSettings {
+            override_directory: Some(directory_with(&[(
+                "de/confirmation/subject.txt",
+                "Willkommen!",
+            )])),
+            ..bundled()
+        };
+
+        assert_err!(EmailTemplates::load(&settings));
+    }
+
+    #[test]
+    fn untranslated_emails_fall_back_to_the_default_locale() {
+        let templates = EmailTemplates::load(&bundled()).unwrap();
+        let render = |template: EmailTemplate, locale| {
+            templates
+                .render(template, Some(locale), template.sample_context())
+                .unwrap()
+                .subject
+        };
+
+        assert_eq!(render(EmailTemplate::Confirmation, "fr"), "Bienvenue !");
+        assert_eq!(
+            render(EmailTemplate::PasswordReset, "fr"),
+            "Reset your password"
+        );
+        assert_eq!(render(EmailTemplate::Confirmation, "xx"), "Welcome!");
+    }
+
+    #[test]
+    fn accept_language_is_ordered_by_quality() {
+        assert_eq!(
+            parse_accept_language("de;q=0.5, fr-CA, en;q=0.8, *;q=0.1"),
+            ["fr-CA", "en", "de"]
+        );
+        assert_eq!(parse_accept_language("fr;q=0, en"), ["en"]);
+        assert!(parse_accept_language("").is_empty());
+    }
+
+    #[test]
+    fn the_requested_locale_wins_over_the_browser_languages() {
+        let templates = EmailTemplates::load(&bundled()).unwrap();
+
+        assert_eq!(
+            templates.negotiate_locale(Some("en"), Some("fr")),
+            Some("en".into())
+        );
+        assert_eq!(
+            templates.negotiate_locale(Some("klingon"), Some("de, fr-BE;q=0.9")),
+            Some("fr".into())
+        );
+        assert_eq!(templates.negotiate_locale(None, Some("de")), None);
+    }
+}
@@ -0,0 +1,215 @@
+//! Erasing subscribers at their request, as GDPR entitles them to.
+//!
+//! The subscription row is kept, stripped of the email address and name,
+//! so that delivery statistics still add up. A salted hash of the address
+//! is kept as well, to refuse it if it is ever submitted again.
+
+use std::collections::HashSet;
+
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::domain::SubscriberEmail;
+
+/// The form in which erased addresses are remembered.
+///
+/// `normalised_email` is the normalised form of the address, see
+/// `SubscriberEmail::normalised`, so that the spellings of an address that
+/// reach the same mailbox stay suppressed too.
+///
+/// The salt keeps the hashes from being matched against a list of known
+/// addresses by anyone who gets hold of the table without the configuration.
+pub fn suppression_hash(salt: &SecretString, normalised_email: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.expose_secret().as_bytes());
+    hasher.update(b":");
+    hasher.update(normalised_email.trim().to_lowercase().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Whether `email` belongs to an erased subscriber.
+#[tracing::instrument(name = "Check whether an email is suppressed", skip_all)]
+pub async fn is_suppressed(
+    executor: impl PgExecutor<'_>,
+    salt: &SecretString,
+    email: &SubscriberEmail,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM suppressed_emails WHERE email_hash = $1) AS "exists!""#,
+        suppression_hash(salt, email.normalised()),
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// The hashes among `hashes` that belong to erased subscribers.
+#[tracing::instrument(name = "Find suppressed emails", skip_all)]
+pub async fn suppressed_hashes(
+    executor: impl PgExecutor<'_>,
+    hashes: &[String],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let suppressed = sqlx::query_scalar!(
+        r#"SELECT email_hash FROM suppressed_emails WHERE email_hash = ANY($1)"#,
+        hashes,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(suppressed.into_iter().collect())
+}
+
+/// Erase a subscriber, returning the status it had before.
+///
+/// Returns `None` if there is no such subscriber. Erasing a subscriber
+/// twice is harmless, the second time only reports the `erased` status.
+#[tracing::instrument(name = "Erase a subscriber", skip(transaction, salt))]
+pub async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    salt: &SecretString,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT normalised_email, status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let Some(subscriber) = subscriber else {
+        return Ok(None);
+    };
+    if subscriber.status == "erased" {
+        return Ok(Some(subscriber.status));
+    }
+
+    let now = OffsetDateTime::now_utc();
+    sqlx::query!(
+        r#"INSERT INTO suppressed_emails (email_hash, suppressed_at) VALUES ($1, $2)
+        ON CONFLICT (email_hash) DO NOTHING"#,
+        suppression_hash(salt, &subscriber.normalised_email),
+        now,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"DELETE FROM topic_opt_outs WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"DELETE FROM list_memberships WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"DELETE FROM data_export_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    // When and how consent was given is not identifying on its own and
+    // stays as proof, where it came from goes.
+    sqlx::query!(
+        r#"UPDATE consents SET ip_address = NULL, user_agent = NULL WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    // The email columns are unique and required, so they get a placeholder
+    // that cannot receive mail rather than being cleared.
+    sqlx::query!(
+        r#"UPDATE subscriptions
+        SET email = $2, normalised_email = $2, name = '', status = 'erased', erased_at = $3
+        WHERE id = $1"#,
+        subscriber_id,
+        erased_email(subscriber_id),
+        now,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(Some(subscriber.status))
+}
+
+fn erased_email(subscriber_id: Uuid) -> String {
+    format!("erased-{}@erased.invalid", subscriber_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_hash_ignores_case_and_surrounding_whitespace() {
+        let salt = SecretString::from("salt");
+
+        assert_eq!(
+            suppression_hash(&salt, " Ursula@Example.com"),
+            suppression_hash(&salt, "ursula@example.com")
+        );
+    }
+
+    #[test]
+    fn the_hash_depends_on_the_salt() {
+        assert_ne!(
+            suppression_hash(&SecretString::from("salt"), "ursula@example.com"),
+            suppression_hash(&SecretString::from("pepper"), "ursula@example.com")
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{domain::SubscriberEmail, email_client::EmailClient};
+
+/// Send a published issue to every confirmed subscriber of its list that
+/// has not received it yet, recording the outcome of each attempt.
+///
+/// Subscribers who paused delivery are skipped, as are those who turned off
+/// every topic the issue is about.
+#[tracing::instrument(name = "Deliver a newsletter issue", skip(database, email_client))]
+pub async fn deliver_issue(
+    database: &PgPool,
+    email_client: &EmailClient,
+    issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let issue = sqlx::query!(
+        r#"SELECT title, text_content, html_content, list_id FROM newsletter_issues
+        WHERE issue_id = $1 AND published_at IS NOT NULL"#,
+        issue_id,
+    )
+    .fetch_one(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let recipients = sqlx::query!(
+        r#"SELECT s.id, s.email AS "email: SubscriberEmail" FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        WHERE m.list_id = $2 AND m.status = 'confirmed'
+        AND s.delivery_paused_at IS NULL
+        AND (
+            NOT EXISTS (SELECT 1 FROM issue_topics WHERE issue_id = $1)
+            OR EXISTS (
+                SELECT 1 FROM issue_topics it
+                WHERE it.issue_id = $1 AND NOT EXISTS (
+                    SELECT 1 FROM topic_opt_outs o
+                    WHERE o.subscriber_id = s.id AND o.topic_id = it.topic_id
+                )
+            )
+        )
+        AND s.id NOT IN (SELECT subscriber_id FROM issue_deliveries WHERE issue_id = $1)"#,
+        issue_id,
+        issue.list_id,
+    )
+    .fetch_all(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    for recipient in recipients {
+        let outcome = match email_client
+            .send_email(recipient.email, &issue.title, &issue.html_content, &issue.text_content)
+            .await
+        {
+            Ok(()) => "sent",
+            Err(e) => {
+                tracing::error!(subscriber_id = %recipient.id, "Failed to deliver issue: {:?}", e);
+                "failed"
+            }
+        };
+
+        sqlx::query!(
+            r#"INSERT INTO issue_deliveries (issue_id, subscriber_id, outcome, attempted_at)
+            VALUES ($1, $2, $3, $4)"#,
+            issue_id,
+            recipient.id,
+            outcome,
+            OffsetDateTime::now_utc(),
+        )
+        .execute(database)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,20 @@
+pub mod audit;
+pub mod authentication;
+pub mod authorization;
+pub mod client;
 pub mod configuration;
+pub mod consent;
+pub mod data_export;
+pub mod deliverability;
 pub mod domain;
 pub mod email_client;
+pub mod email_policy;
+pub mod email_templates;
+pub mod erasure;
+pub mod issue_delivery;
+pub mod lists;
+pub mod rate_limit;
 pub mod routes;
+pub mod signup_challenge;
 pub mod startup;
 pub mod telemetry;
@@ -0,0 +1,242 @@
+//! The newsletters of a deployment, and who subscribed to which.
+//!
+//! Subscribers share their address, name and language across lists, but
+//! join and confirm each list on its own: the subscription token sent in a
+//! confirmation email is for one list. Signups to `/subscriptions` go to
+//! the default list.
+//!
+//! A list may have topics, which issues can be about. Subscribers receive
+//! every topic of their lists unless they turned it off.
+
+use sqlx::{PgExecutor, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, serde::Serialize)]
+pub struct List {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Topic {
+    pub topic_id: Uuid,
+    pub slug: String,
+    pub name: String,
+}
+
+/// A list a subscriber joined, as shown to them in their data export.
+#[derive(serde::Serialize)]
+pub struct ListMembership {
+    list: String,
+    status: String,
+    /// The slugs of the topics of the list the subscriber turned off.
+    opted_out_topics: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    subscribed_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    confirmed_at: Option<OffsetDateTime>,
+}
+
+#[tracing::instrument(name = "Get a list", skip(executor))]
+pub async fn get_list(
+    executor: impl PgExecutor<'_>,
+    slug: &str,
+) -> Result<Option<List>, sqlx::Error> {
+    sqlx::query_as!(
+        List,
+        r#"SELECT list_id, slug, name FROM lists WHERE slug = $1"#,
+        slug,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// Every list, by name.
+#[tracing::instrument(name = "List the lists", skip(executor))]
+pub async fn all_lists(executor: impl PgExecutor<'_>) -> Result<Vec<List>, sqlx::Error> {
+    sqlx::query_as!(
+        List,
+        r#"SELECT list_id, slug, name FROM lists ORDER BY name"#
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// The list that takes the signups of `/subscriptions`, created by the
+/// migrations.
+#[tracing::instrument(name = "Get the default list", skip(executor))]
+pub async fn get_default_list(executor: impl PgExecutor<'_>) -> Result<List, sqlx::Error> {
+    sqlx::query_as!(
+        List,
+        r#"SELECT list_id, slug, name FROM lists WHERE is_default"#
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// Add a subscriber to a list, pending their confirmation.
+///
+/// Joining again before confirming starts over. Returns `false` if the
+/// subscriber already confirmed they are on the list.
+#[tracing::instrument(name = "Join a list", skip(transaction))]
+pub async fn join_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let joined = sqlx::query_scalar!(
+        r#"INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, 'pending_confirmation', $3)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET subscribed_at = EXCLUDED.subscribed_at
+        WHERE list_memberships.status = 'pending_confirmation'
+        RETURNING status"#,
+        list_id,
+        subscriber_id,
+        OffsetDateTime::now_utc(),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(joined.is_some())
+}
+
+/// Confirm a subscriber is on a list, returning the status the membership
+/// had before.
+#[tracing::instrument(name = "Confirm a list membership", skip(transaction))]
+pub async fn confirm_membership(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<String, sqlx::Error> {
+    let previous_status = sqlx::query_scalar!(
+        r#"SELECT status FROM list_memberships
+        WHERE list_id = $1 AND subscriber_id = $2
+        FOR UPDATE"#,
+        list_id,
+        subscriber_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"UPDATE list_memberships
+        SET status = 'confirmed', confirmed_at = COALESCE(confirmed_at, now())
+        WHERE list_id = $1 AND subscriber_id = $2"#,
+        list_id,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(previous_status)
+}
+
+/// The lists a subscriber joined, in the order they joined them.
+#[tracing::instrument(name = "List memberships", skip(executor))]
+pub async fn list_memberships(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListMembership>, sqlx::Error> {
+    sqlx::query_as!(
+        ListMembership,
+        r#"SELECT l.slug AS list, m.status, m.subscribed_at, m.confirmed_at,
+            ARRAY(
+                SELECT t.slug FROM topic_opt_outs o
+                JOIN topics t ON t.topic_id = o.topic_id
+                WHERE o.subscriber_id = m.subscriber_id AND t.list_id = m.list_id
+                ORDER BY t.slug
+            ) AS "opted_out_topics!"
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY m.subscribed_at"#,
+        subscriber_id,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// The topics of a list, by name.
+#[tracing::instrument(name = "List topics", skip(executor))]
+pub async fn list_topics(
+    executor: impl PgExecutor<'_>,
+    list_id: Uuid,
+) -> Result<Vec<Topic>, sqlx::Error> {
+    sqlx::query_as!(
+        Topic,
+        r#"SELECT topic_id, slug, name FROM topics WHERE list_id = $1 ORDER BY name"#,
+        list_id,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
+
+/// Whether `slug` can name a list or a topic in URLs: lowercase letters,
+/// digits and dashes.
+pub fn is_valid_slug(slug: &str) -> bool {
+    (1..=64).contains(&slug.len())
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_slug;
+
+    #[test]
+    fn slugs_are_lowercase_words_joined_by_dashes() {
+        for slug in ["newsletter", "weekly-digest", "2025-events"] {
+            assert!(is_valid_slug(slug), "{}", slug);
+        }
+    }
+
+    #[test]
+    fn anything_else_is_not_a_slug() {
+        for slug in [
+            "",
+            "Weekly",
+            "weekly digest",
+            "-weekly",
+            "weekly/",
+            "digest-",
+        ] {
+            assert!(!is_valid_slug(slug), "{}", slug);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Limits on how often the public endpoints can be called.
+//!
+//! Each endpoint that sends an email to an address taken from a form could
+//! otherwise be used to flood someone's inbox with our emails. Requests are
+//! counted per client address and per submitted email address, in fixed
+//! windows, either in memory or in Postgres when several replicas run.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    client::ClientIp,
+    configuration::{RateLimit, RateLimitBackend, RateLimitSettings},
+    domain::SubscriberEmail,
+    startup::AppState,
+};
+
+/// Forms on the public endpoints are a few fields long.
+const MAX_FORM_BYTES: usize = 16 * 1024;
+
+/// How many counter updates go by between two sweeps of expired counters.
+const SWEEP_EVERY: u64 = 1000;
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    settings: Arc<RateLimitSettings>,
+    store: Store,
+    hits: Arc<AtomicU64>,
+}
+
+#[derive(Clone)]
+enum Store {
+    InProcess(Arc<Mutex<HashMap<String, Counter>>>),
+    Postgres(PgPool),
+}
+
+struct Counter {
+    window_ends_at: OffsetDateTime,
+    count: u32,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings, database: PgPool) -> Self {
+        let store = match settings.backend {
+            RateLimitBackend::InProcess => Store::InProcess(Default::default()),
+            RateLimitBackend::Postgres => Store::Postgres(database),
+        };
+        Self {
+            settings: Arc::new(settings),
+            store,
+            hits: Default::default(),
+        }
+    }
+
+    /// Count a request against `key`, returning how long to wait before
+    /// trying again if it goes over `limit`.
+    #[tracing::instrument(name = "Count a rate limited request", skip_all)]
+    async fn hit(&self, key: &str, limit: &RateLimit) -> Result<Option<Duration>, sqlx::Error> {
+        let now = OffsetDateTime::now_utc();
+        let window_ends_at = window_end(now, limit.window_seconds);
+        let sweep = self
+            .hits
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(SWEEP_EVERY);
+
+        let count = match &self.store {
+            Store::InProcess(counters) => {
+                let mut counters = counters.lock().unwrap();
+                if sweep {
+                    counters.retain(|_, counter| counter.window_ends_at > now);
+                }
+                let counter = counters.entry(key.to_owned()).or_insert(Counter {
+                    window_ends_at,
+                    count: 0,
+                });
+                if counter.window_ends_at != window_ends_at {
+                    counter.window_ends_at = window_ends_at;
+                    counter.count = 0;
+                }
+                counter.count += 1;
+                counter.count
+            }
+            Store::Postgres(database) => {
+                if sweep {
+                    sweep_expired_counters(database).await?;
+                }
+                count_in_database(database, key, window_ends_at).await?
+            }
+        };
+
+        Ok((count > limit.requests).then(|| window_ends_at - now))
+    }
+}
+
+/// The end of the fixed window `now` falls in. Windows are aligned on the
+/// Unix epoch, so that replicas agree on them.
+fn window_end(now: OffsetDateTime, window_seconds: u64) -> OffsetDateTime {
+    let window_seconds = window_seconds.max(1) as i64;
+    let timestamp = now.unix_timestamp();
+    let start = timestamp - timestamp.rem_euclid(window_seconds);
+    OffsetDateTime::from_unix_timestamp(start + window_seconds).expect("Timestamp out of range")
+}
+
+async fn count_in_database(
+    database: &PgPool,
+    key: &str,
+    window_ends_at: OffsetDateTime,
+) -> Result<u32, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"INSERT INTO rate_limit_counters (key, window_ends_at, count)
+        VALUES ($1, $2, 1)
+        ON CONFLICT (key) DO UPDATE
+        SET count = CASE
+                WHEN rate_limit_counters.window_ends_at = EXCLUDED.window_ends_at
+                THEN rate_limit_counters.count + 1
+                ELSE 1
+            END,
+            window_ends_at = EXCLUDED.window_ends_at
+        RETURNING count"#,
+        key,
+        window_ends_at,
+    )
+    .fetch_one(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(count.max(0) as u32)
+}
+
+async fn sweep_expired_counters(database: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(r#"DELETE FROM rate_limit_counters WHERE window_ends_at < now()"#)
+        .execute(database)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+struct EmailField {
+    email: Option<String>,
+}
+
+/// The counter key of an email address.
+///
+/// The spellings that reach the same mailbox share a counter, and the
+/// address is hashed to keep it out of `rate_limit_counters`.
+fn email_key(email: &SubscriberEmail) -> String {
+    let hash = Sha256::digest(email.normalised().to_lowercase().as_bytes());
+    format!("email:{:x}", hash)
+}
+
+type MiddlewareFuture = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+/// Build a middleware rejecting requests over the limits with a 429, telling
+/// the client when to come back in `Retry-After`.
+///
+/// Client addresses are counted separately in each `scope`, so that using up
+/// the budget of the public forms does not lock anyone out of logging in, or
+/// the other way around. Email addresses share one count across scopes, as
+/// it is their inbox that gets flooded.
+///
+/// Meant to be wrapped with `axum::middleware::from_fn_with_state` and mounted
+/// with `route_layer`.
+pub fn rate_limit(
+    scope: &'static str,
+) -> impl Fn(State<AppState>, Request, Next) -> MiddlewareFuture + Clone + Send + Sync + 'static {
+    move |state, request, next| Box::pin(limit(scope, state, request, next))
+}
+
+async fn limit(
+    scope: &'static str,
+    State(AppState {
+        rate_limiter,
+        email_normalisation,
+        ..
+    }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let settings = &rate_limiter.settings;
+    if !settings.enabled {
+        return next.run(request).await;
+    }
+
+    if let Some(ClientIp(ip)) = request.extensions().get::<ClientIp>().copied() {
+        match rate_limiter
+            .hit(&format!("ip:{}:{}", scope, ip), &settings.per_ip)
+            .await
+        {
+            Ok(None) => {}
+            Ok(Some(retry_after)) => {
+                tracing::warn!(%ip, scope, "client address over its rate limit");
+                return too_many_requests(retry_after);
+            }
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    let is_form = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_FORM_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    // Invalid addresses are refused by the endpoints, no email is sent to them.
+    let email = serde_urlencoded::from_bytes::<EmailField>(&bytes)
+        .ok()
+        .and_then(|form| form.email)
+        .and_then(|email| SubscriberEmail::parse(email.trim().to_string()).ok())
+        .map(|email| email_normalisation.apply(email));
+
+    if let Some(email) = email {
+        match rate_limiter
+            .hit(&email_key(&email), &settings.per_email)
+            .await
+        {
+            Ok(None) => {}
+            Ok(Some(retry_after)) => {
+                tracing::warn!("email address over its rate limit");
+                return too_many_requests(retry_after);
+            }
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.whole_seconds().max(1);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::window_end;
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+
+    #[test]
+    fn windows_are_aligned_on_the_epoch() {
+        // 2025-05-03 09:44:12 UTC, in the window ending at 10:00:00
+        assert_eq!(window_end(at(1746265452), 3600), at(1746266400));
+    }
+
+    #[test]
+    fn the_end_of_a_window_starts_the_next_one() {
+        // 2025-05-03 10:00:00 UTC, in the window ending at 10:10:00
+        assert_eq!(window_end(at(1746266400), 600), at(1746267000));
+    }
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::startup::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(serde::Deserialize)]
+pub struct AuditLogParameters {
+    /// Only return entries older than this entry id, as found in `next_cursor`.
+    before: Option<i64>,
+    limit: Option<i64>,
+    action: Option<String>,
+    actor_id: Option<Uuid>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct AuditLogEntry {
+    id: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    occurred_at: OffsetDateTime,
+    actor_type: String,
+    actor_id: Option<Uuid>,
+    action: String,
+    target_type: String,
+    target_id: String,
+    request_id: Option<String>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+}
+
+#[derive(serde::Serialize)]
+pub struct AuditLogPage {
+    entries: Vec<AuditLogEntry>,
+    /// Pass as `before` to fetch the next page, absent on the last page.
+    next_cursor: Option<i64>,
+}
+
+/// List audit log entries, newest first.
+#[tracing::instrument(name = "List audit log entries", skip(database, params))]
+pub async fn list_audit_log(
+    State(AppState { database, .. }): State<AppState>,
+    Query(params): Query<AuditLogParameters>,
+) -> Result<Json<AuditLogPage>, StatusCode> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // One extra row tells whether there is a next page.
+    let mut entries = sqlx::query_as!(
+        AuditLogEntry,
+        r#"SELECT id, occurred_at, actor_type, actor_id, action, target_type, target_id,
+            request_id, before, after
+        FROM audit_log
+        WHERE ($1::bigint IS NULL OR id < $1)
+            AND ($2::text IS NULL OR action = $2)
+            AND ($3::uuid IS NULL OR actor_id = $3)
+            AND ($4::text IS NULL OR target_type = $4)
+            AND ($5::text IS NULL OR target_id = $5)
+        ORDER BY id DESC
+        LIMIT $6"#,
+        params.before,
+        params.action,
+        params.actor_id,
+        params.target_type,
+        params.target_id,
+        limit + 1,
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let next_cursor = if entries.len() as i64 > limit {
+        entries.truncate(limit as usize);
+        entries.last().map(|e| e.id)
+    } else {
+        None
+    };
+
+    Ok(Json(AuditLogPage {
+        entries,
+        next_cursor,
+    }))
+}
@@ -0,0 +1,326 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use axum_extra::extract::{CookieJar, Form};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::{csrf_token, verify_csrf_token, AuthenticatedUser},
+    authorization::Permission,
+    issue_delivery::deliver_issue,
+    lists::{all_lists, get_default_list, get_list, list_topics, List, Topic},
+    startup::AppState,
+};
+
+use super::{render, CsrfFormData};
+
+const ISSUES_PAGE: &str = "/admin/dashboard/issues";
+const STATS_PAGE: &str = "/admin/dashboard/stats";
+
+struct IssueRow {
+    issue_id: Uuid,
+    title: String,
+    list_name: String,
+    /// The names of the topics the issue is about, if it is not for the
+    /// whole list.
+    topics: Option<String>,
+    created_at: String,
+    published_at: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/issues.html")]
+struct IssuesTemplate {
+    csrf_token: String,
+    can_publish: bool,
+    lists: Vec<List>,
+    topics: Vec<TopicOption>,
+    issues: Vec<IssueRow>,
+}
+
+struct TopicOption {
+    topic_id: Uuid,
+    list_name: String,
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct IssueFormData {
+    title: String,
+    text_content: String,
+    html_content: String,
+    /// The slug of the list to send the issue to, the default list if none.
+    list: Option<String>,
+    /// The topics of the list the issue is about, none for the whole list.
+    #[serde(default)]
+    topics: Vec<Uuid>,
+    csrf_token: String,
+}
+
+#[tracing::instrument(name = "Admin dashboard issues", skip(database, jar, user))]
+pub async fn issues_page(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+) -> Result<(CookieJar, Html<String>), StatusCode> {
+    let issues = sqlx::query!(
+        r#"SELECT i.issue_id, i.title, l.name AS list_name, i.created_at, i.published_at,
+            (
+                SELECT string_agg(t.name, ', ' ORDER BY t.name) FROM issue_topics it
+                JOIN topics t ON t.topic_id = it.topic_id
+                WHERE it.issue_id = i.issue_id
+            ) AS topics
+        FROM newsletter_issues i
+        JOIN lists l ON l.list_id = i.list_id
+        ORDER BY i.created_at DESC"#
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .into_iter()
+    .map(|row| IssueRow {
+        issue_id: row.issue_id,
+        title: row.title,
+        list_name: row.list_name,
+        topics: row.topics,
+        created_at: row.created_at.format(&Rfc3339).unwrap_or_default(),
+        published_at: row
+            .published_at
+            .map(|p| p.format(&Rfc3339).unwrap_or_default()),
+    })
+    .collect();
+
+    let lists = all_lists(&database)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let topics = sqlx::query_as!(
+        TopicOption,
+        r#"SELECT t.topic_id, l.name AS list_name, t.name FROM topics t
+        JOIN lists l ON l.list_id = t.list_id
+        ORDER BY l.name, t.name"#
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let (jar, csrf_token) = csrf_token(jar);
+    let page = render(IssuesTemplate {
+        csrf_token,
+        can_publish: user.role.can(Permission::PublishIssues),
+        lists,
+        topics,
+        issues,
+    })?;
+    Ok((jar, page))
+}
+
+#[tracing::instrument(
+    name = "Save a newsletter issue draft",
+    skip(database, jar, user, request_id, data),
+    fields(user_id = %user.user_id)
+)]
+pub async fn save_issue(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Form(mut data): Form<IssueFormData>,
+) -> Result<Redirect, StatusCode> {
+    if !verify_csrf_token(&jar, &data.csrf_token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if [&data.title, &data.text_content, &data.html_content]
+        .iter()
+        .any(|field| field.trim().is_empty())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let list = match data.list.as_deref().filter(|slug| !slug.is_empty()) {
+        Some(slug) => get_list(&database, slug)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::BAD_REQUEST)?,
+        None => get_default_list(&database)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    };
+
+    // A resubmitted multi-select can send the same topic twice.
+    data.topics.sort();
+    data.topics.dedup();
+    let list_topics = list_topics(&database, list.list_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let topics: Vec<&Topic> = list_topics
+        .iter()
+        .filter(|topic| data.topics.contains(&topic.topic_id))
+        .collect();
+    // Topics of another list would silently narrow the issue to nobody.
+    if topics.len() != data.topics.len() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let Ok(mut transaction) = database.begin().await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO newsletter_issues
+        (issue_id, title, text_content, html_content, list_id, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+        issue_id,
+        data.title,
+        data.text_content,
+        data.html_content,
+        list.list_id,
+        user.user_id,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    sqlx::query!(
+        r#"INSERT INTO issue_topics (issue_id, topic_id)
+        SELECT $1, * FROM UNNEST($2::uuid[])"#,
+        issue_id,
+        &topics
+            .iter()
+            .map(|topic| topic.topic_id)
+            .collect::<Vec<_>>(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "issue.create",
+        target_type: "issue",
+        target_id: issue_id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "title": data.title,
+            "list": list.slug,
+            "topics": topics.iter().map(|topic| &topic.slug).collect::<Vec<_>>(),
+        })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if transaction.commit().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Redirect::to(ISSUES_PAGE))
+}
+
+/// Publish a draft and start delivering it to the confirmed subscribers of
+/// its list.
+///
+/// Delivery happens in the background; its progress shows on the stats page.
+#[tracing::instrument(
+    name = "Publish a newsletter issue",
+    skip(database, email_client, jar, user, request_id, data),
+    fields(user_id = %user.user_id)
+)]
+pub async fn publish_issue(
+    State(AppState {
+        database,
+        email_client,
+        ..
+    }): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(issue_id): Path<Uuid>,
+    Form(data): Form<CsrfFormData>,
+) -> Result<Redirect, StatusCode> {
+    if !verify_csrf_token(&jar, &data.csrf_token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let Ok(mut transaction) = database.begin().await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let published_at = sqlx::query_scalar!(
+        r#"SELECT published_at FROM newsletter_issues WHERE issue_id = $1 FOR UPDATE"#,
+        issue_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match published_at {
+        None => return Err(StatusCode::NOT_FOUND),
+        Some(Some(_)) => return Err(StatusCode::CONFLICT),
+        Some(None) => {}
+    }
+
+    sqlx::query!(
+        r#"UPDATE newsletter_issues SET published_at = $1 WHERE issue_id = $2"#,
+        OffsetDateTime::now_utc(),
+        issue_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "issue.publish",
+        target_type: "issue",
+        target_id: issue_id.to_string(),
+        before: Some(serde_json::json!({ "published": false })),
+        after: Some(serde_json::json!({ "published": true })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if transaction.commit().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = deliver_issue(&database, &email_client, issue_id).await {
+            tracing::error!("Failed to deliver issue {}: {:?}", issue_id, e);
+        }
+    });
+
+    Ok(Redirect::to(STATS_PAGE))
+}
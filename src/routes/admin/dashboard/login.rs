@@ -0,0 +1,196 @@
+use askama::Template;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use secrecy::SecretString;
+
+use crate::{
+    authentication::{
+        complete_second_factor, create_session, csrf_token, delete_session, get_session_user,
+        is_two_factor_enabled, record_failed_second_factor, session_cookie, validate_credentials,
+        verify_csrf_token, verify_second_factor, AuthError, Credentials, SESSION_COOKIE,
+    },
+    startup::AppState,
+};
+
+use super::{render, CsrfFormData, LOGIN_PAGE};
+
+/// Where users land after logging in, which every role can read.
+const HOME_PAGE: &str = "/admin/dashboard/stats";
+const TWO_FACTOR_PAGE: &str = "/admin/dashboard/login/two-factor";
+
+#[derive(Template)]
+#[template(path = "admin/login.html")]
+struct LoginTemplate<'a> {
+    csrf_token: &'a str,
+    error: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/two_factor.html")]
+struct TwoFactorTemplate<'a> {
+    csrf_token: &'a str,
+    error: Option<&'a str>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LoginFormData {
+    username: String,
+    password: SecretString,
+    csrf_token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TwoFactorFormData {
+    code: String,
+    csrf_token: String,
+}
+
+pub async fn login_page(jar: CookieJar) -> Result<(CookieJar, Response), StatusCode> {
+    let (jar, csrf_token) = csrf_token(jar);
+    let page = render(LoginTemplate {
+        csrf_token: &csrf_token,
+        error: None,
+    })?;
+    Ok((jar, page.into_response()))
+}
+
+#[tracing::instrument(
+    name = "Admin dashboard login",
+    skip(database, jar, data),
+    fields(username = %data.username, user_id = tracing::field::Empty)
+)]
+pub async fn submit_login(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    Form(data): Form<LoginFormData>,
+) -> Result<(CookieJar, Response), StatusCode> {
+    if !verify_csrf_token(&jar, &data.csrf_token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let credentials = Credentials {
+        username: data.username,
+        password: data.password,
+    };
+
+    let user_id = match validate_credentials(credentials, &database).await {
+        Ok(user_id) => user_id,
+        Err(AuthError::InvalidCredentials) => {
+            let page = render(LoginTemplate {
+                csrf_token: &data.csrf_token,
+                error: Some("Invalid username or password."),
+            })?;
+            return Ok((jar, (StatusCode::UNAUTHORIZED, page).into_response()));
+        }
+        Err(AuthError::Unexpected(e)) => {
+            tracing::error!("Failed to validate credentials: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let Ok(two_factor_required) = is_two_factor_enabled(&database, user_id).await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let Ok(session_id) = create_session(&database, user_id, two_factor_required).await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let next_page = if two_factor_required {
+        TWO_FACTOR_PAGE
+    } else {
+        HOME_PAGE
+    };
+    Ok((
+        jar.add(session_cookie(session_id)),
+        Redirect::to(next_page).into_response(),
+    ))
+}
+
+pub async fn two_factor_page(jar: CookieJar) -> Result<(CookieJar, Response), StatusCode> {
+    let (jar, csrf_token) = csrf_token(jar);
+    let page = render(TwoFactorTemplate {
+        csrf_token: &csrf_token,
+        error: None,
+    })?;
+    Ok((jar, page.into_response()))
+}
+
+#[tracing::instrument(name = "Admin dashboard second factor", skip(database, jar, data))]
+pub async fn submit_two_factor(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    Form(data): Form<TwoFactorFormData>,
+) -> Result<Response, StatusCode> {
+    if !verify_csrf_token(&jar, &data.csrf_token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let Some(session_id) = jar.get(SESSION_COOKIE) else {
+        return Ok(Redirect::to(LOGIN_PAGE).into_response());
+    };
+
+    let Ok(session_user) = get_session_user(&database, session_id.value()).await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let Some(session_user) = session_user.filter(|s| s.second_factor_pending) else {
+        return Ok(Redirect::to(LOGIN_PAGE).into_response());
+    };
+
+    match verify_second_factor(&database, session_user.user_id, &data.code).await {
+        Ok(()) => {}
+        Err(AuthError::InvalidCredentials) => {
+            if record_failed_second_factor(&database, session_id.value())
+                .await
+                .is_err()
+            {
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+            let page = render(TwoFactorTemplate {
+                csrf_token: &data.csrf_token,
+                error: Some("Invalid code."),
+            })?;
+            return Ok((StatusCode::UNAUTHORIZED, page).into_response());
+        }
+        Err(AuthError::Unexpected(e)) => {
+            tracing::error!("Failed to verify second factor: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if complete_second_factor(&database, session_id.value())
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Redirect::to(HOME_PAGE).into_response())
+}
+
+#[tracing::instrument(name = "Admin dashboard logout", skip(database, jar, data))]
+pub async fn submit_logout(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    Form(data): Form<CsrfFormData>,
+) -> Result<(CookieJar, Redirect), StatusCode> {
+    if !verify_csrf_token(&jar, &data.csrf_token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if let Some(session_id) = jar.get(SESSION_COOKIE) {
+        if delete_session(&database, session_id.value()).await.is_err() {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let jar = jar.remove(Cookie::build(SESSION_COOKIE).path("/admin"));
+    Ok((jar, Redirect::to(LOGIN_PAGE)))
+}
@@ -0,0 +1,43 @@
+//! The server-rendered admin UI.
+//!
+//! Every form carries a CSRF token, see `authentication::csrf_token`.
+
+mod issues;
+mod login;
+mod stats;
+mod subscribers;
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::{authentication::AuthenticatedUser, routes::html::render};
+
+pub use issues::*;
+pub use login::*;
+pub use stats::*;
+pub use subscribers::*;
+
+const LOGIN_PAGE: &str = "/admin/dashboard/login";
+
+/// The body of forms that submit nothing but their CSRF token.
+#[derive(serde::Deserialize)]
+pub struct CsrfFormData {
+    csrf_token: String,
+}
+
+/// Send visitors without a session to the login page rather than answering 401.
+pub async fn redirect_to_login(
+    user: Result<AuthenticatedUser, StatusCode>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match user {
+        Ok(_) => next.run(request).await,
+        Err(StatusCode::UNAUTHORIZED) => Redirect::to(LOGIN_PAGE).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
@@ -0,0 +1,77 @@
+use askama::Template;
+use axum::{extract::State, http::StatusCode, response::Html};
+use axum_extra::extract::CookieJar;
+use time::format_description::well_known::Rfc3339;
+
+use crate::{authentication::csrf_token, startup::AppState};
+
+use super::render;
+
+struct IssueStats {
+    title: String,
+    published_at: String,
+    sent: i64,
+    failed: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/stats.html")]
+struct StatsTemplate {
+    csrf_token: String,
+    subscriber_counts: Vec<(String, i64)>,
+    issues: Vec<IssueStats>,
+}
+
+#[tracing::instrument(name = "Admin dashboard delivery stats", skip(database, jar))]
+pub async fn stats_page(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+) -> Result<(CookieJar, Html<String>), StatusCode> {
+    let subscriber_counts = sqlx::query!(
+        r#"SELECT status, COUNT(*) AS "count!" FROM subscriptions GROUP BY status ORDER BY status"#
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .into_iter()
+    .map(|row| (row.status, row.count))
+    .collect();
+
+    let issues = sqlx::query!(
+        r#"SELECT
+            i.title,
+            i.published_at AS "published_at!",
+            COUNT(*) FILTER (WHERE d.outcome = 'sent') AS "sent!",
+            COUNT(*) FILTER (WHERE d.outcome = 'failed') AS "failed!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_deliveries d USING (issue_id)
+        WHERE i.published_at IS NOT NULL
+        GROUP BY i.issue_id
+        ORDER BY i.published_at DESC"#
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .into_iter()
+    .map(|row| IssueStats {
+        title: row.title,
+        published_at: row.published_at.format(&Rfc3339).unwrap_or_default(),
+        sent: row.sent,
+        failed: row.failed,
+    })
+    .collect();
+
+    let (jar, csrf_token) = csrf_token(jar);
+    let page = render(StatsTemplate {
+        csrf_token,
+        subscriber_counts,
+        issues,
+    })?;
+    Ok((jar, page))
+}
@@ -0,0 +1,77 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Html,
+};
+use axum_extra::extract::CookieJar;
+use time::format_description::well_known::Rfc3339;
+
+use crate::{authentication::csrf_token, startup::AppState};
+
+use super::render;
+
+/// The page lists the most recent subscribers only.
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SubscriberFilter {
+    status: Option<String>,
+}
+
+struct SubscriberRow {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscribers.html")]
+struct SubscribersTemplate {
+    csrf_token: String,
+    status: Option<String>,
+    subscribers: Vec<SubscriberRow>,
+}
+
+#[tracing::instrument(name = "Admin dashboard subscribers", skip(database, jar))]
+pub async fn subscribers_page(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    Query(filter): Query<SubscriberFilter>,
+) -> Result<(CookieJar, Html<String>), StatusCode> {
+    let status = filter.status.filter(|s| !s.is_empty());
+
+    let rows = sqlx::query!(
+        r#"SELECT email, name, status, subscribed_at FROM subscriptions
+        WHERE $1::TEXT IS NULL OR status = $1
+        ORDER BY subscribed_at DESC
+        LIMIT $2"#,
+        status,
+        PAGE_SIZE,
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let subscribers = rows
+        .into_iter()
+        .map(|row| SubscriberRow {
+            email: row.email,
+            name: row.name,
+            status: row.status,
+            subscribed_at: row.subscribed_at.format(&Rfc3339).unwrap_or_default(),
+        })
+        .collect();
+
+    let (jar, csrf_token) = csrf_token(jar);
+    let page = render(SubscribersTemplate {
+        csrf_token,
+        status,
+        subscribers,
+    })?;
+    Ok((jar, page))
+}
@@ -0,0 +1,202 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    email_policy::DomainRule,
+    startup::AppState,
+};
+
+#[derive(serde::Serialize)]
+pub struct EmailDomainRule {
+    domain: String,
+    rule: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailDomainRuleData {
+    rule: DomainRule,
+}
+
+/// List the domains admins blocked or allowed.
+#[tracing::instrument(name = "List email domain rules", skip(database))]
+pub async fn list_email_domain_rules(
+    State(AppState { database, .. }): State<AppState>,
+) -> Result<Json<Vec<EmailDomainRule>>, StatusCode> {
+    sqlx::query_as!(
+        EmailDomainRule,
+        r#"SELECT domain, rule, created_at FROM email_domain_rules ORDER BY domain"#
+    )
+    .fetch_all(&database)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Block or allow signups from a domain and its subdomains.
+///
+/// Allowing a domain lets its addresses in even if it is on the disposable
+/// domain list.
+#[tracing::instrument(
+    name = "Set an email domain rule",
+    skip(database, user, request_id, data)
+)]
+pub async fn set_email_domain_rule(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(domain): Path<String>,
+    Json(data): Json<EmailDomainRuleData>,
+) -> StatusCode {
+    let Some(domain) = normalise_domain(&domain) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let previous = sqlx::query_scalar!(
+        r#"SELECT rule FROM email_domain_rules WHERE domain = $1 FOR UPDATE"#,
+        domain,
+    )
+    .fetch_optional(&mut *transaction)
+    .await;
+    let Ok(previous) = previous else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let result = sqlx::query!(
+        r#"INSERT INTO email_domain_rules (domain, rule)
+        VALUES ($1, $2)
+        ON CONFLICT (domain) DO UPDATE SET rule = EXCLUDED.rule"#,
+        domain,
+        data.rule.as_str(),
+    )
+    .execute(&mut *transaction)
+    .await;
+    if let Err(e) = result {
+        tracing::error!("Failed to execute query: {:?}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "email_domain.rule_change",
+        target_type: "email_domain",
+        target_id: domain,
+        before: previous.map(|rule| serde_json::json!({ "rule": rule })),
+        after: Some(serde_json::json!({ "rule": data.rule.as_str() })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/// Remove the rule of a domain, leaving its addresses to the other checks.
+#[tracing::instrument(name = "Delete an email domain rule", skip(database, user, request_id))]
+pub async fn delete_email_domain_rule(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(domain): Path<String>,
+) -> StatusCode {
+    let Some(domain) = normalise_domain(&domain) else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let previous = sqlx::query_scalar!(
+        r#"DELETE FROM email_domain_rules WHERE domain = $1 RETURNING rule"#,
+        domain,
+    )
+    .fetch_optional(&mut *transaction)
+    .await;
+    let previous = match previous {
+        Ok(Some(previous)) => previous,
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Failed to execute query: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "email_domain.rule_delete",
+        target_type: "email_domain",
+        target_id: domain,
+        before: Some(serde_json::json!({ "rule": previous })),
+        after: None,
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/// The domain in lowercase, if it looks like a domain name.
+fn normalise_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().to_lowercase();
+    let valid = !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    valid.then_some(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalise_domain;
+
+    #[test]
+    fn domains_are_lowercased() {
+        assert_eq!(
+            normalise_domain("Mail.Example.COM").as_deref(),
+            Some("mail.example.com")
+        );
+    }
+
+    #[test]
+    fn anything_but_a_domain_is_refused() {
+        for domain in [
+            "",
+            "ursula@example.com",
+            ".example.com",
+            "example..com",
+            "exa mple.com",
+        ] {
+            assert_eq!(normalise_domain(domain), None, "{}", domain);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    lists::{all_lists, get_list, is_valid_slug, list_topics, List, Topic},
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct TopicData {
+    slug: String,
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListData {
+    slug: String,
+    name: String,
+}
+
+/// The lists people can subscribe to, by name.
+#[tracing::instrument(name = "List mailing lists", skip(database))]
+pub async fn list_lists(
+    State(AppState { database, .. }): State<AppState>,
+) -> Result<Json<Vec<List>>, StatusCode> {
+    all_lists(&database)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Create a list, open to signups at `/lists/{slug}/subscriptions`.
+#[tracing::instrument(name = "Create a mailing list", skip(database, user, request_id, data))]
+pub async fn create_list(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Json(data): Json<ListData>,
+) -> Result<(StatusCode, Json<List>), StatusCode> {
+    let name = data.name.trim();
+    if !is_valid_slug(&data.slug) || name.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let Ok(mut transaction) = database.begin().await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let list = sqlx::query_as!(
+        List,
+        r#"INSERT INTO lists (list_id, slug, name) VALUES ($1, $2, $3)
+        ON CONFLICT (slug) DO NOTHING
+        RETURNING list_id, slug, name"#,
+        Uuid::new_v4(),
+        data.slug,
+        name,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::CONFLICT)?;
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "list.create",
+        target_type: "list",
+        target_id: list.list_id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({ "slug": list.slug, "name": list.name })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if transaction.commit().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok((StatusCode::CREATED, Json(list)))
+}
+
+/// The topics of a list, by name.
+#[tracing::instrument(name = "List the topics of a mailing list", skip(database))]
+pub async fn list_list_topics(
+    State(AppState { database, .. }): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Json<Vec<Topic>>, StatusCode> {
+    let list = get_list(&database, &slug)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    list_topics(&database, list.list_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Add a topic to a list. Its subscribers receive it until they turn it off.
+#[tracing::instrument(
+    name = "Create a mailing list topic",
+    skip(database, user, request_id, data)
+)]
+pub async fn create_list_topic(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(slug): Path<String>,
+    Json(data): Json<TopicData>,
+) -> Result<(StatusCode, Json<Topic>), StatusCode> {
+    let list = get_list(&database, &slug)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let name = data.name.trim();
+    if !is_valid_slug(&data.slug) || name.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let Ok(mut transaction) = database.begin().await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let topic = sqlx::query_as!(
+        Topic,
+        r#"INSERT INTO topics (topic_id, list_id, slug, name) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (list_id, slug) DO NOTHING
+        RETURNING topic_id, slug, name"#,
+        Uuid::new_v4(),
+        list.list_id,
+        data.slug,
+        name,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::CONFLICT)?;
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "topic.create",
+        target_type: "topic",
+        target_id: topic.topic_id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "list": list.slug,
+            "slug": topic.slug,
+            "name": topic.name,
+        })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if transaction.commit().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok((StatusCode::CREATED, Json(topic)))
+}
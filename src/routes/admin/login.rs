@@ -0,0 +1,64 @@
+use axum::{extract::State, http::StatusCode, Form, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::SecretString;
+
+use crate::{
+    authentication::{
+        create_session, is_two_factor_enabled, session_cookie, validate_credentials, AuthError,
+        Credentials,
+    },
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct LoginData {
+    username: String,
+    password: SecretString,
+}
+
+#[derive(serde::Serialize)]
+pub struct LoginResponse {
+    /// The session is only usable once `POST /admin/login/two-factor` succeeds.
+    two_factor_required: bool,
+}
+
+#[tracing::instrument(
+    name = "Admin login",
+    skip(database, jar, data),
+    fields(username = %data.username, user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    Form(data): Form<LoginData>,
+) -> Result<(CookieJar, Json<LoginResponse>), StatusCode> {
+    let credentials = Credentials {
+        username: data.username,
+        password: data.password,
+    };
+
+    let user_id = match validate_credentials(credentials, &database).await {
+        Ok(user_id) => user_id,
+        Err(AuthError::InvalidCredentials) => return Err(StatusCode::UNAUTHORIZED),
+        Err(AuthError::Unexpected(e)) => {
+            tracing::error!("Failed to validate credentials: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let Ok(two_factor_required) = is_two_factor_enabled(&database, user_id).await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let Ok(session_id) = create_session(&database, user_id, two_factor_required).await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    Ok((
+        jar.add(session_cookie(session_id)),
+        Json(LoginResponse {
+            two_factor_required,
+        }),
+    ))
+}
@@ -0,0 +1,24 @@
+use axum::{extract::State, http::StatusCode};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+
+use crate::{
+    authentication::{delete_session, SESSION_COOKIE},
+    startup::AppState,
+};
+
+#[tracing::instrument(name = "Admin logout", skip(database, jar))]
+pub async fn logout(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+) -> Result<(CookieJar, StatusCode), StatusCode> {
+    let Some(session_id) = jar.get(SESSION_COOKIE) else {
+        return Ok((jar, StatusCode::OK));
+    };
+
+    if delete_session(&database, session_id.value()).await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let jar = jar.remove(Cookie::build(SESSION_COOKIE).path("/admin"));
+    Ok((jar, StatusCode::OK))
+}
@@ -0,0 +1,33 @@
+mod audit_log;
+mod dashboard;
+mod email_domains;
+mod lists;
+mod login;
+mod logout;
+mod password_reset;
+mod security_policy;
+mod subscriber_consents;
+mod subscriber_data_export;
+mod subscriber_erasure;
+mod subscriber_export;
+mod subscriber_import;
+mod subscribers;
+mod two_factor;
+mod users;
+
+pub use audit_log::*;
+pub use dashboard::*;
+pub use email_domains::*;
+pub use lists::*;
+pub use login::*;
+pub use logout::*;
+pub use password_reset::*;
+pub use security_policy::*;
+pub use subscriber_consents::*;
+pub use subscriber_data_export::*;
+pub use subscriber_erasure::*;
+pub use subscriber_export::*;
+pub use subscriber_import::*;
+pub use subscribers::*;
+pub use two_factor::*;
+pub use users::*;
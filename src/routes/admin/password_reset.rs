@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Html,
+    Form,
+};
+use secrecy::SecretString;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::{
+        issue_password_reset_token, reset_password, validate_new_password, AuthError,
+    },
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    email_templates::{EmailError, EmailTemplate, EmailTemplates},
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetRequestData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetParameters {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetData {
+    token: String,
+    password: SecretString,
+}
+
+/// Email a password reset link to the admin registered with `email`.
+///
+/// The response is the same whether or not such an admin exists, and the
+/// email is sent in the background so that timing does not tell either.
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(database, email_client, email_templates, data)
+)]
+pub async fn request_password_reset(
+    State(AppState {
+        database,
+        email_client,
+        email_templates,
+        base_url,
+        ..
+    }): State<AppState>,
+    Form(data): Form<PasswordResetRequestData>,
+) -> StatusCode {
+    let Ok(email) = SubscriberEmail::parse(data.email) else {
+        return StatusCode::OK;
+    };
+
+    let reset_token = match issue_password_reset_token(&database, email.as_ref()).await {
+        Ok(Some(reset_token)) => reset_token,
+        Ok(None) => return StatusCode::OK,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    tracing::info!(user_id = %reset_token.user_id, "issued a password reset token");
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset_email(
+            &email_client,
+            &email_templates,
+            email,
+            &base_url,
+            &reset_token.token,
+        )
+        .await
+        {
+            tracing::error!("Failed to send password reset email: {:?}", e);
+        }
+    });
+
+    StatusCode::OK
+}
+
+#[tracing::instrument(
+    name = "Send a password reset email",
+    skip(email_client, email_templates, recipient, token)
+)]
+async fn send_password_reset_email(
+    email_client: &EmailClient,
+    email_templates: &EmailTemplates,
+    recipient: SubscriberEmail,
+    base_url: &str,
+    token: &str,
+) -> Result<(), EmailError> {
+    let reset_link = format!("{}/admin/password-reset/confirm?token={}", base_url, token);
+
+    let email = email_templates.render(
+        EmailTemplate::PasswordReset,
+        None,
+        minijinja::context! { reset_link },
+    )?;
+
+    email_client
+        .send_email(recipient, &email.subject, &email.html, &email.text)
+        .await?;
+    Ok(())
+}
+
+/// The page the reset link lands on, asking for the new password.
+pub async fn password_reset_form(Query(params): Query<PasswordResetParameters>) -> Html<String> {
+    let token: String = params
+        .token
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>Reset your password</title></head>
+<body>
+    <form action="/admin/password-reset/confirm" method="post">
+        <input type="hidden" name="token" value="{}">
+        <label>New password <input type="password" name="password" minlength="12" maxlength="128" required></label>
+        <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#,
+        token
+    ))
+}
+
+/// Set a new password using the token from a reset link.
+///
+/// Every existing session of the admin is ended.
+#[tracing::instrument(name = "Reset a password", skip(database, request_id, data))]
+pub async fn confirm_password_reset(
+    State(AppState { database, .. }): State<AppState>,
+    request_id: RequestId,
+    Form(data): Form<PasswordResetData>,
+) -> StatusCode {
+    if validate_new_password(&data.password).is_err() {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let user_id = match reset_password(&mut transaction, &data.token, data.password).await {
+        Ok(user_id) => user_id,
+        Err(AuthError::InvalidCredentials) => {
+            tracing::warn!("refused a password reset with an unknown or expired token");
+            return StatusCode::BAD_REQUEST;
+        }
+        Err(AuthError::Unexpected(e)) => {
+            tracing::error!("Failed to reset password: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    let entry = AuditEntry {
+        actor: Actor::User(user_id),
+        action: "user.password_reset",
+        target_type: "user",
+        target_id: user_id.to_string(),
+        before: None,
+        after: None,
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    tracing::info!(%user_id, "password was reset, all sessions were ended");
+    StatusCode::OK
+}
@@ -0,0 +1,93 @@
+use axum::{extract::State, http::StatusCode, Json};
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    startup::AppState,
+};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SecurityPolicy {
+    require_two_factor: bool,
+}
+
+#[tracing::instrument(name = "Get the security policy", skip(database))]
+pub async fn get_security_policy(
+    State(AppState { database, .. }): State<AppState>,
+) -> Result<Json<SecurityPolicy>, StatusCode> {
+    sqlx::query_as!(
+        SecurityPolicy,
+        r#"SELECT require_two_factor FROM security_policy"#
+    )
+    .fetch_one(&database)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Update the security policy.
+///
+/// Requiring two-factor authentication does not lock anybody out: users
+/// without a second factor can still log in, but are limited to enrolling
+/// one until they do.
+#[tracing::instrument(
+    name = "Update the security policy",
+    skip(database, user, request_id, policy)
+)]
+pub async fn update_security_policy(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Json(policy): Json<SecurityPolicy>,
+) -> StatusCode {
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let previous = sqlx::query_as!(
+        SecurityPolicy,
+        r#"SELECT require_two_factor FROM security_policy FOR UPDATE"#
+    )
+    .fetch_one(&mut *transaction)
+    .await;
+
+    let Ok(previous) = previous else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE security_policy SET require_two_factor = $1"#,
+        policy.require_two_factor,
+    )
+    .execute(&mut *transaction)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Failed to execute query: {:?}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "security_policy.update",
+        target_type: "security_policy",
+        target_id: "default".into(),
+        before: serde_json::to_value(&previous).ok(),
+        after: serde_json::to_value(&policy).ok(),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
@@ -0,0 +1,37 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    consent::{list_consents, Consent},
+    startup::AppState,
+};
+
+/// How a subscriber opted in, oldest step first.
+#[tracing::instrument(name = "List a subscriber's consents", skip(database))]
+pub async fn list_subscriber_consents(
+    State(AppState { database, .. }): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<Vec<Consent>>, StatusCode> {
+    let subscriber_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM subscriptions WHERE id = $1) AS "exists!""#,
+        subscriber_id,
+    )
+    .fetch_one(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !subscriber_exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let consents = list_consents(&database, subscriber_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(consents))
+}
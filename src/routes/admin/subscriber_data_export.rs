@@ -0,0 +1,41 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    data_export::{compile_subscriber_archive, SubscriberArchive},
+    startup::AppState,
+};
+
+/// Everything held about a subscriber, to answer a data access request
+/// received outside of the self-service flow.
+#[tracing::instrument(name = "Export a subscriber's data", skip(database, user, request_id))]
+pub async fn export_subscriber_data(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<SubscriberArchive, StatusCode> {
+    let archive = compile_subscriber_archive(&database, subscriber_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "subscriber.data_export",
+        target_type: "subscriber",
+        target_id: subscriber_id.to_string(),
+        before: None,
+        after: None,
+    };
+    record_audit_entry(&database, &request_id, entry)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(archive)
+}
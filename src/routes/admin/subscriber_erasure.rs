@@ -0,0 +1,62 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    erasure::erase_subscriber,
+    startup::AppState,
+};
+
+/// Erase a subscriber's personal data, keeping their address suppressed.
+#[tracing::instrument(
+    name = "Erase a subscriber's data",
+    skip(database, suppression_salt, user, request_id)
+)]
+pub async fn erase_subscriber_data(
+    State(AppState {
+        database,
+        suppression_salt,
+        ..
+    }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(subscriber_id): Path<Uuid>,
+) -> StatusCode {
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let previous_status =
+        match erase_subscriber(&mut transaction, &suppression_salt, subscriber_id).await {
+            Ok(Some(previous_status)) => previous_status,
+            Ok(None) => return StatusCode::NOT_FOUND,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+    if previous_status != "erased" {
+        let entry = AuditEntry {
+            actor: Actor::User(user.user_id),
+            action: "subscriber.erase",
+            target_type: "subscriber",
+            target_id: subscriber_id.to_string(),
+            before: Some(serde_json::json!({ "status": previous_status })),
+            after: Some(serde_json::json!({ "status": "erased" })),
+        };
+        if record_audit_entry(&mut *transaction, &request_id, entry)
+            .await
+            .is_err()
+        {
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
@@ -0,0 +1,280 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{stream, TryStreamExt};
+use sqlx::PgPool;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    startup::AppState,
+};
+
+use super::SubscriberFilters;
+
+/// Lines buffered between the database and a slow client.
+const BUFFERED_LINES: usize = 256;
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// JSON Lines, one subscriber object per line.
+    Jsonl,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+        }
+    }
+
+    fn header(&self) -> Option<String> {
+        match self {
+            Self::Csv => Some(csv_line(&[
+                "id",
+                "email",
+                "name",
+                "status",
+                "subscribed_at",
+                "confirmed_at",
+            ])),
+            Self::Jsonl => None,
+        }
+    }
+
+    fn line(&self, subscriber: &ExportedSubscriber) -> String {
+        match self {
+            Self::Csv => {
+                let format = |timestamp: Option<OffsetDateTime>| {
+                    timestamp
+                        .and_then(|t| t.format(&Rfc3339).ok())
+                        .unwrap_or_default()
+                };
+                csv_line(&[
+                    &subscriber.id.to_string(),
+                    &subscriber.email,
+                    &subscriber.name,
+                    &subscriber.status,
+                    &format(Some(subscriber.subscribed_at)),
+                    &format(subscriber.confirmed_at),
+                ])
+            }
+            Self::Jsonl => {
+                let mut line = serde_json::to_string(subscriber)
+                    .expect("Subscribers can always be serialized");
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExportParameters {
+    #[serde(flatten)]
+    filters: SubscriberFilters,
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    #[serde(with = "time::serde::rfc3339")]
+    subscribed_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    confirmed_at: Option<OffsetDateTime>,
+}
+
+/// Export every subscriber matching the filters of the subscriber search,
+/// oldest first.
+///
+/// Rows are sent as they come out of the database, so the size of the
+/// export does not matter. If the query fails halfway, the response is
+/// cut short rather than silently truncated.
+#[tracing::instrument(name = "Export subscribers", skip(database, user, request_id))]
+pub async fn export_subscribers(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Query(params): Query<ExportParameters>,
+) -> Response {
+    let format = params.format;
+    let filters = &params.filters;
+    let format_time =
+        |timestamp: Option<OffsetDateTime>| timestamp.and_then(|t| t.format(&Rfc3339).ok());
+    // The email and name search terms may be addresses or names, which
+    // stay out of the audit log: only whether they were used is recorded.
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "subscriber.export",
+        target_type: "subscriber_export",
+        target_id: Uuid::new_v4().to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "format": format,
+            "filters": {
+                "status": filters.status,
+                "email": filters.email.is_some(),
+                "name": filters.name.is_some(),
+                "subscribed_after": format_time(filters.subscribed_after),
+                "subscribed_before": format_time(filters.subscribed_before),
+                "flagged": filters.flagged,
+            },
+        })),
+    };
+    if record_audit_entry(&database, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let (sender, receiver) = mpsc::channel(BUFFERED_LINES);
+    tokio::spawn(stream_subscribers(database, params, sender));
+
+    let lines = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|line| (line, receiver))
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"subscribers.{}\"",
+                    format.extension()
+                ),
+            ),
+        ],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+async fn stream_subscribers(
+    database: PgPool,
+    params: ExportParameters,
+    sender: mpsc::Sender<Result<String, sqlx::Error>>,
+) {
+    let format = params.format;
+    let filters = params.filters;
+
+    if let Some(header) = format.header() {
+        if sender.send(Ok(header)).await.is_err() {
+            return;
+        }
+    }
+
+    let mut subscribers = sqlx::query_as!(
+        ExportedSubscriber,
+        r#"SELECT id, email, name, status, subscribed_at, confirmed_at
+        FROM subscriptions
+        WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::text IS NULL OR email ILIKE $2)
+            AND ($3::text IS NULL OR name ILIKE $3)
+            AND ($4::timestamptz IS NULL OR subscribed_at >= $4)
+            AND ($5::timestamptz IS NULL OR subscribed_at < $5)
+            AND ($6::bool IS NULL OR (flagged_reason IS NOT NULL) = $6)
+        ORDER BY subscribed_at, id"#,
+        filters.status,
+        filters.email_pattern(),
+        filters.name_pattern(),
+        filters.subscribed_after,
+        filters.subscribed_before,
+        filters.flagged,
+    )
+    .fetch(&database);
+
+    loop {
+        let line = match subscribers.try_next().await {
+            Ok(Some(subscriber)) => Ok(format.line(&subscriber)),
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("Failed to execute query: {:?}", e);
+                Err(e)
+            }
+        };
+        let failed = line.is_err();
+
+        // Stop reading from the database as soon as the client goes away.
+        if sender.send(line).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// A CSV line, quoting the fields that need it.
+///
+/// Fields that a spreadsheet would run as a formula are prefixed with `'`,
+/// as names and addresses come from the public signup form.
+fn csv_line(fields: &[&str]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| {
+            let field = if field.starts_with(['=', '+', '-', '@']) {
+                format!("'{}", field)
+            } else {
+                field.to_string()
+            };
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_fields_are_not_quoted() {
+        assert_eq!(
+            csv_line(&["ursula@example.com", "Ursula"]),
+            "ursula@example.com,Ursula\n"
+        );
+    }
+
+    #[test]
+    fn fields_with_separators_or_quotes_are_quoted() {
+        assert_eq!(
+            csv_line(&["Le Guin, Ursula", "\"Ursula\"", "line\nbreak"]),
+            "\"Le Guin, Ursula\",\"\"\"Ursula\"\"\",\"line\nbreak\"\n"
+        );
+    }
+
+    #[test]
+    fn fields_that_look_like_formulas_are_escaped() {
+        assert_eq!(
+            csv_line(&["=HYPERLINK(\"x\")", "+1", "-1", "@SUM(A1)", "Ursula"]),
+            "\"'=HYPERLINK(\"\"x\"\")\",'+1,'-1,'@SUM(A1),Ursula\n"
+        );
+    }
+}
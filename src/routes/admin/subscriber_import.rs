@@ -0,0 +1,670 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use csv_async::{AsyncReaderBuilder, ErrorKind, StringRecord, Trim};
+use futures_util::TryStreamExt;
+use secrecy::SecretString;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::AuthenticatedUser,
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    erasure::{suppressed_hashes, suppression_hash},
+    lists::get_default_list,
+    routes::{generate_random_subscription_token, send_confirmation_email},
+    startup::AppState,
+};
+
+/// Rows upserted per transaction.
+const BATCH_SIZE: usize = 500;
+
+#[derive(serde::Serialize)]
+pub struct ImportSummary {
+    import_id: Uuid,
+    imported: i32,
+    failed: i32,
+    /// Where to download the rows that were rejected, as CSV.
+    error_report: String,
+}
+
+/// How the consent of an imported subscriber was obtained.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConsentBasis {
+    /// The subscriber already confirmed their address with the previous provider.
+    Confirmed,
+    /// The subscriber still has to confirm their address.
+    Unconfirmed,
+}
+
+impl ConsentBasis {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "confirmed" => Some(Self::Confirmed),
+            "unconfirmed" | "" => Some(Self::Unconfirmed),
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::Unconfirmed => "pending_confirmation",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ImportRow {
+    line: u64,
+    subscriber: NewSubscriber,
+    consent_basis: ConsentBasis,
+}
+
+/// A rejected row. The rejected values are left out on purpose, the line
+/// number is enough to find them in the uploaded file.
+struct ImportError {
+    line: u64,
+    message: String,
+}
+
+#[derive(Debug)]
+struct Columns {
+    email: usize,
+    name: usize,
+    consent_basis: usize,
+}
+
+impl Columns {
+    fn from_headers(headers: &StringRecord) -> Option<Self> {
+        let position = |column: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(column));
+        Some(Self {
+            email: position("email")?,
+            name: position("name")?,
+            consent_basis: position("consent_basis")?,
+        })
+    }
+}
+
+fn parse_row(
+    record: &StringRecord,
+    columns: &Columns,
+    line: u64,
+) -> Result<ImportRow, &'static str> {
+    let field = |index| record.get(index).unwrap_or_default().to_string();
+
+    let email =
+        SubscriberEmail::parse(field(columns.email)).map_err(|_| "invalid email address")?;
+    let name = SubscriberName::parse(field(columns.name)).map_err(|_| "invalid name")?;
+    let consent_basis = ConsentBasis::parse(&field(columns.consent_basis))
+        .ok_or("consent_basis must be either confirmed or unconfirmed")?;
+
+    Ok(ImportRow {
+        line,
+        subscriber: NewSubscriber { email, name },
+        consent_basis,
+    })
+}
+
+/// Import subscribers from the CSV file in the request body.
+///
+/// The file needs `email`, `name` and `consent_basis` columns. Subscribers
+/// are added to the default list. Rows with a `confirmed` consent basis are
+/// imported as confirmed subscribers, the others are sent a confirmation
+/// email. Existing subscribers are updated, but never lose their confirmed
+/// status.
+///
+/// The file is processed as it is received and committed in batches, so
+/// the rows that were valid are kept even if the upload breaks off.
+#[tracing::instrument(
+    name = "Import subscribers",
+    skip(
+        database,
+        email_client,
+        email_templates,
+        base_url,
+        suppression_salt,
+        email_normalisation,
+        user,
+        request_id,
+        body
+    ),
+    fields(user_id = %user.user_id, import_id = tracing::field::Empty)
+)]
+pub async fn import_subscribers(
+    State(AppState {
+        database,
+        email_client,
+        email_templates,
+        base_url,
+        suppression_salt,
+        email_normalisation,
+        ..
+    }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    body: Body,
+) -> Result<Json<ImportSummary>, StatusCode> {
+    let body = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    let mut reader = AsyncReaderBuilder::new()
+        .flexible(true)
+        .trim(Trim::All)
+        .create_reader(body);
+
+    let Ok(headers) = reader.headers().await else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let Some(columns) = Columns::from_headers(headers) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let import_id = Uuid::new_v4();
+    tracing::Span::current().record("import_id", tracing::field::display(&import_id));
+    create_import(&database, import_id, user.user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let list = get_default_list(&database)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // The line each normalised email was first seen on, as a single
+    // statement cannot upsert the same row twice.
+    let mut first_seen: HashMap<String, u64> = HashMap::new();
+    let mut rows = Vec::with_capacity(BATCH_SIZE);
+    let mut errors = Vec::new();
+    let mut record = StringRecord::new();
+    let (mut imported, mut failed) = (0, 0);
+    // Counted rather than taken from the reader, which has no position for
+    // some malformed rows, and errors are stored per line.
+    let mut line: u64 = 1;
+
+    loop {
+        line += 1;
+        let done = match reader.read_record(&mut record).await {
+            Ok(false) => true,
+            Ok(true) => {
+                match parse_row(&record, &columns, line) {
+                    Ok(mut row) => {
+                        row.subscriber.email = email_normalisation.apply(row.subscriber.email);
+                        match first_seen.get(row.subscriber.email.normalised()) {
+                            Some(first_line) => errors.push(ImportError {
+                                line,
+                                message: format!("duplicate of line {}", first_line),
+                            }),
+                            None => {
+                                first_seen
+                                    .insert(row.subscriber.email.normalised().to_owned(), line);
+                                rows.push(row);
+                            }
+                        }
+                    }
+                    Err(message) => errors.push(ImportError {
+                        line,
+                        message: message.to_string(),
+                    }),
+                }
+                false
+            }
+            Err(e) => match e.kind() {
+                ErrorKind::Io(e) => {
+                    tracing::warn!("Failed to read the uploaded file: {:?}", e);
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+                _ => {
+                    errors.push(ImportError {
+                        line,
+                        message: "malformed row".to_string(),
+                    });
+                    false
+                }
+            },
+        };
+
+        if done || rows.len() + errors.len() >= BATCH_SIZE {
+            let mut transaction = database
+                .begin()
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            reject_suppressed(&mut transaction, &suppression_salt, &mut rows, &mut errors)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            imported += rows.len() as i32;
+            failed += errors.len() as i32;
+            let confirmations = import_batch(
+                &mut transaction,
+                import_id,
+                list.list_id,
+                &mut rows,
+                &mut errors,
+            )
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            transaction
+                .commit()
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            if !confirmations.is_empty() {
+                let email_client = email_client.clone();
+                let email_templates = email_templates.clone();
+                let base_url = base_url.clone();
+                tokio::spawn(async move {
+                    for (subscriber, token) in confirmations {
+                        if let Err(e) = send_confirmation_email(
+                            &email_client,
+                            &email_templates,
+                            subscriber,
+                            None,
+                            base_url.clone(),
+                            &token,
+                        )
+                        .await
+                        {
+                            tracing::error!("Failed to send confirmation email: {:?}", e);
+                        }
+                    }
+                });
+            }
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    let mut transaction = database
+        .begin()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    complete_import(&mut transaction, import_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "subscriber.import",
+        target_type: "subscriber_import",
+        target_id: import_id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({ "imported": imported, "failed": failed })),
+    };
+    record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    transaction
+        .commit()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tracing::info!(imported, failed, "imported subscribers");
+    Ok(Json(ImportSummary {
+        import_id,
+        imported,
+        failed,
+        error_report: format!("/admin/subscribers/imports/{}/errors", import_id),
+    }))
+}
+
+/// Download the rows an import rejected, as CSV.
+#[tracing::instrument(name = "Download an import error report", skip(database))]
+pub async fn download_import_errors(
+    State(AppState { database, .. }): State<AppState>,
+    Path(import_id): Path<Uuid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let import_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM subscriber_imports WHERE import_id = $1) AS "exists!""#,
+        import_id,
+    )
+    .fetch_one(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !import_exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let errors = sqlx::query!(
+        r#"SELECT line, message FROM subscriber_import_errors
+        WHERE import_id = $1 ORDER BY line"#,
+        import_id,
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut report = String::from("line,error\n");
+    for error in errors {
+        report.push_str(&format!(
+            "{},\"{}\"\n",
+            error.line,
+            error.message.replace('"', "\"\"")
+        ));
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"import-{}-errors.csv\"", import_id),
+            ),
+        ],
+        report,
+    ))
+}
+
+#[tracing::instrument(name = "Create a subscriber import", skip(database))]
+async fn create_import(
+    database: &PgPool,
+    import_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO subscriber_imports (import_id, created_by, created_at)
+        VALUES ($1, $2, $3)"#,
+        import_id,
+        user_id,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+/// Move the rows of erased subscribers from `rows` to `errors`.
+async fn reject_suppressed(
+    transaction: &mut Transaction<'_, Postgres>,
+    salt: &SecretString,
+    rows: &mut Vec<ImportRow>,
+    errors: &mut Vec<ImportError>,
+) -> Result<(), sqlx::Error> {
+    let hashes: Vec<String> = rows
+        .iter()
+        .map(|row| suppression_hash(salt, row.subscriber.email.normalised()))
+        .collect();
+    let suppressed = suppressed_hashes(&mut **transaction, &hashes).await?;
+    if suppressed.is_empty() {
+        return Ok(());
+    }
+
+    let mut hashes = hashes.iter();
+    rows.retain(|row| {
+        let erased = hashes.next().is_some_and(|hash| suppressed.contains(hash));
+        if erased {
+            errors.push(ImportError {
+                line: row.line,
+                message: "the subscriber asked for their data to be erased".to_string(),
+            });
+        }
+        !erased
+    });
+    Ok(())
+}
+
+/// Upsert `rows` as members of the list and store `errors`, draining both.
+///
+/// Returns the subscribers new to the list who still have to confirm, with
+/// their subscription token.
+#[tracing::instrument(
+    name = "Import a batch of subscribers",
+    skip(transaction, rows, errors),
+    fields(rows = rows.len(), errors = errors.len())
+)]
+async fn import_batch(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    list_id: Uuid,
+    rows: &mut Vec<ImportRow>,
+    errors: &mut Vec<ImportError>,
+) -> Result<Vec<(NewSubscriber, String)>, sqlx::Error> {
+    let mut ids = Vec::with_capacity(rows.len());
+    let mut emails = Vec::with_capacity(rows.len());
+    let mut normalised_emails = Vec::with_capacity(rows.len());
+    let mut names = Vec::with_capacity(rows.len());
+    let mut statuses = Vec::with_capacity(rows.len());
+    for row in rows.iter() {
+        ids.push(Uuid::new_v4());
+        emails.push(row.subscriber.email.as_ref().to_owned());
+        normalised_emails.push(row.subscriber.email.normalised().to_owned());
+        names.push(row.subscriber.name.as_ref().to_owned());
+        statuses.push(row.consent_basis.status().to_owned());
+    }
+
+    let now = OffsetDateTime::now_utc();
+    sqlx::query!(
+        r#"INSERT INTO subscriptions
+            (id, email, normalised_email, name, subscribed_at, status, confirmed_at)
+        SELECT id, email, normalised_email, name, $6::timestamptz, status,
+            CASE WHEN status = 'confirmed' THEN $6::timestamptz END
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $5::text[])
+            AS t(id, email, normalised_email, name, status)
+        ON CONFLICT (normalised_email) DO UPDATE SET
+            name = EXCLUDED.name,
+            status = CASE
+                WHEN subscriptions.status = 'confirmed' THEN 'confirmed'
+                ELSE EXCLUDED.status
+            END,
+            confirmed_at = COALESCE(subscriptions.confirmed_at, EXCLUDED.confirmed_at)"#,
+        &ids,
+        &emails,
+        &normalised_emails,
+        &names,
+        &statuses,
+        now,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    // `xmax` is only zero for rows that were inserted rather than updated.
+    let upserted = sqlx::query!(
+        r#"WITH memberships AS (
+            INSERT INTO list_memberships
+                (list_id, subscriber_id, status, subscribed_at, confirmed_at)
+            SELECT $1, s.id, t.status, $4::timestamptz,
+                CASE WHEN t.status = 'confirmed' THEN $4::timestamptz END
+            FROM UNNEST($2::text[], $3::text[]) AS t(normalised_email, status)
+            JOIN subscriptions s ON s.normalised_email = t.normalised_email
+            ON CONFLICT (list_id, subscriber_id) DO UPDATE SET
+                status = CASE
+                    WHEN list_memberships.status = 'confirmed' THEN 'confirmed'
+                    ELSE EXCLUDED.status
+                END,
+                confirmed_at = COALESCE(list_memberships.confirmed_at, EXCLUDED.confirmed_at)
+            RETURNING subscriber_id, status, (xmax = 0) AS inserted
+        )
+        SELECT s.id, s.normalised_email, m.status, m.inserted AS "inserted!"
+        FROM memberships m
+        JOIN subscriptions s ON s.id = m.subscriber_id"#,
+        list_id,
+        &normalised_emails,
+        &statuses,
+        now,
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let mut subscribers: HashMap<String, NewSubscriber> = rows
+        .drain(..)
+        .map(|row| (row.subscriber.email.normalised().to_owned(), row.subscriber))
+        .collect();
+    let mut confirmations = Vec::new();
+    for row in upserted {
+        if !row.inserted || row.status != "pending_confirmation" {
+            continue;
+        }
+        if let Some(subscriber) = subscribers.remove(&row.normalised_email) {
+            confirmations.push((row.id, subscriber, generate_random_subscription_token()));
+        }
+    }
+
+    let subscriber_ids: Vec<Uuid> = confirmations.iter().map(|(id, _, _)| *id).collect();
+    let tokens: Vec<String> = confirmations
+        .iter()
+        .map(|(_, _, token)| token.clone())
+        .collect();
+    sqlx::query!(
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        SELECT *, $3 FROM UNNEST($1::text[], $2::uuid[])"#,
+        &tokens,
+        &subscriber_ids,
+        list_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let (lines, messages): (Vec<i64>, Vec<String>) = errors
+        .drain(..)
+        .map(|error| (error.line as i64, error.message))
+        .unzip();
+    sqlx::query!(
+        r#"INSERT INTO subscriber_import_errors (import_id, line, message)
+        SELECT $1, * FROM UNNEST($2::bigint[], $3::text[])"#,
+        import_id,
+        &lines,
+        &messages,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"UPDATE subscriber_imports
+        SET imported_rows = imported_rows + $1, failed_rows = failed_rows + $2
+        WHERE import_id = $3"#,
+        emails.len() as i32,
+        lines.len() as i32,
+        import_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(confirmations
+        .into_iter()
+        .map(|(_, subscriber, token)| (subscriber, token))
+        .collect())
+}
+
+#[tracing::instrument(name = "Complete a subscriber import", skip(transaction))]
+async fn complete_import(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriber_imports SET completed_at = $1 WHERE import_id = $2"#,
+        OffsetDateTime::now_utc(),
+        import_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_none, assert_ok, assert_some};
+
+    use super::*;
+
+    fn columns() -> Columns {
+        Columns::from_headers(&StringRecord::from(vec!["email", "name", "consent_basis"])).unwrap()
+    }
+
+    #[test]
+    fn columns_are_found_in_any_order_and_case() {
+        let headers = StringRecord::from(vec!["Name", "extra", "CONSENT_BASIS", "Email"]);
+
+        let columns = Columns::from_headers(&headers).unwrap();
+
+        assert_eq!(
+            (columns.email, columns.name, columns.consent_basis),
+            (3, 0, 2)
+        );
+    }
+
+    #[test]
+    fn every_column_is_required() {
+        let headers = StringRecord::from(vec!["email", "name"]);
+
+        assert_none!(Columns::from_headers(&headers));
+    }
+
+    #[test]
+    fn the_consent_basis_decides_the_status() {
+        let confirmed = StringRecord::from(vec!["ursula@example.com", "Ursula", "confirmed"]);
+        let unconfirmed = StringRecord::from(vec!["ursula@example.com", "Ursula", ""]);
+
+        assert_eq!(
+            parse_row(&confirmed, &columns(), 2).unwrap().consent_basis,
+            ConsentBasis::Confirmed
+        );
+        assert_eq!(
+            parse_row(&unconfirmed, &columns(), 2)
+                .unwrap()
+                .consent_basis,
+            ConsentBasis::Unconfirmed
+        );
+    }
+
+    #[test]
+    fn invalid_rows_are_rejected() {
+        for record in [
+            vec!["not-an-email", "Ursula", "confirmed"],
+            vec!["ursula@example.com", "", "confirmed"],
+            vec!["ursula@example.com", "Ursula", "implied"],
+            vec!["ursula@example.com"],
+        ] {
+            assert_err!(parse_row(&StringRecord::from(record), &columns(), 2));
+        }
+    }
+
+    #[test]
+    fn unknown_consent_bases_are_rejected() {
+        assert_some!(ConsentBasis::parse("Confirmed"));
+        assert_none!(ConsentBasis::parse("legitimate_interest"));
+        assert_ok!(parse_row(
+            &StringRecord::from(vec!["ursula@example.com", "Ursula", "unconfirmed"]),
+            &columns(),
+            2
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::startup::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Which subscribers to look at; every filter is optional.
+#[derive(Debug, serde::Deserialize)]
+pub struct SubscriberFilters {
+    pub status: Option<String>,
+    /// Case-insensitive substring of the email address.
+    pub email: Option<String>,
+    /// Case-insensitive substring of the name.
+    pub name: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub subscribed_after: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub subscribed_before: Option<OffsetDateTime>,
+    /// Whether the email policy flagged the subscriber for review.
+    #[serde(default, deserialize_with = "optional_bool")]
+    pub flagged: Option<bool>,
+}
+
+impl SubscriberFilters {
+    pub fn email_pattern(&self) -> Option<String> {
+        self.email.as_deref().map(like_pattern)
+    }
+
+    pub fn name_pattern(&self) -> Option<String> {
+        self.name.as_deref().map(like_pattern)
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SubscriberSearchParameters {
+    #[serde(flatten)]
+    filters: SubscriberFilters,
+    /// Resume after the last subscriber of the previous page, as found in `next_cursor`.
+    cursor: Option<String>,
+    limit: Option<i64>,
+    #[serde(default)]
+    sort: SortOrder,
+    #[serde(default)]
+    include_total: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberResponse {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    #[serde(with = "time::serde::rfc3339")]
+    subscribed_at: OffsetDateTime,
+    /// Why the email policy flagged the subscriber, if it did.
+    flagged_reason: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberPage {
+    subscribers: Vec<SubscriberResponse>,
+    /// Pass as `cursor` to fetch the next page, absent on the last page.
+    next_cursor: Option<String>,
+    /// Number of subscribers matching the filters, when `include_total` is set.
+    total: Option<i64>,
+}
+
+/// Search subscribers, ordered by `(subscribed_at, id)`.
+///
+/// Subscription tokens are deliberately left out of the response.
+#[tracing::instrument(name = "Search subscribers", skip(database))]
+pub async fn search_subscribers(
+    State(AppState { database, .. }): State<AppState>,
+    Query(params): Query<SubscriberSearchParameters>,
+) -> Result<Json<SubscriberPage>, StatusCode> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (after_subscribed_at, after_id) = match params.cursor.as_deref().map(decode_cursor) {
+        None => (None, None),
+        Some(Some((subscribed_at, id))) => (Some(subscribed_at), Some(id)),
+        Some(None) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let filters = &params.filters;
+    let ascending = matches!(params.sort, SortOrder::Asc);
+
+    // One extra row tells whether there is a next page.
+    let mut subscribers = sqlx::query_as!(
+        SubscriberResponse,
+        r#"SELECT id, email, name, status, subscribed_at, flagged_reason
+        FROM subscriptions
+        WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::text IS NULL OR email ILIKE $2)
+            AND ($3::text IS NULL OR name ILIKE $3)
+            AND ($4::timestamptz IS NULL OR subscribed_at >= $4)
+            AND ($5::timestamptz IS NULL OR subscribed_at < $5)
+            AND ($10::bool IS NULL OR (flagged_reason IS NOT NULL) = $10)
+            AND ($6::timestamptz IS NULL OR CASE
+                WHEN $8 THEN (subscribed_at, id) > ($6, $7::uuid)
+                ELSE (subscribed_at, id) < ($6, $7::uuid)
+            END)
+        ORDER BY
+            CASE WHEN $8 THEN subscribed_at END ASC,
+            CASE WHEN $8 THEN id END ASC,
+            subscribed_at DESC,
+            id DESC
+        LIMIT $9"#,
+        filters.status,
+        filters.email_pattern(),
+        filters.name_pattern(),
+        filters.subscribed_after,
+        filters.subscribed_before,
+        after_subscribed_at,
+        after_id,
+        ascending,
+        limit + 1,
+        filters.flagged,
+    )
+    .fetch_all(&database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let next_cursor = if subscribers.len() as i64 > limit {
+        subscribers.truncate(limit as usize);
+        subscribers
+            .last()
+            .map(|s| encode_cursor(s.subscribed_at, s.id))
+    } else {
+        None
+    };
+
+    let total = if params.include_total {
+        let total = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM subscriptions
+            WHERE ($1::text IS NULL OR status = $1)
+                AND ($2::text IS NULL OR email ILIKE $2)
+                AND ($3::text IS NULL OR name ILIKE $3)
+                AND ($4::timestamptz IS NULL OR subscribed_at >= $4)
+                AND ($5::timestamptz IS NULL OR subscribed_at < $5)
+                AND ($6::bool IS NULL OR (flagged_reason IS NOT NULL) = $6)"#,
+            filters.status,
+            filters.email_pattern(),
+            filters.name_pattern(),
+            filters.subscribed_after,
+            filters.subscribed_before,
+            filters.flagged,
+        )
+        .fetch_one(&database)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Some(total)
+    } else {
+        None
+    };
+
+    Ok(Json(SubscriberPage {
+        subscribers,
+        next_cursor,
+        total,
+    }))
+}
+
+/// Flattened into the search parameters, the filters get every query string
+/// value as a string, so booleans have to be parsed by hand.
+fn optional_bool<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<bool>, D::Error> {
+    let value: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    value
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// An `ILIKE` pattern matching `substring` anywhere, taking its wildcards literally.
+fn like_pattern(substring: &str) -> String {
+    let escaped = substring
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn encode_cursor(subscribed_at: OffsetDateTime, id: Uuid) -> String {
+    format!("{}_{}", subscribed_at.unix_timestamp_nanos(), id)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(OffsetDateTime, Uuid)> {
+    let (timestamp, id) = cursor.split_once('_')?;
+    let subscribed_at = OffsetDateTime::from_unix_timestamp_nanos(timestamp.parse().ok()?).ok()?;
+    Some((subscribed_at, id.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let subscribed_at = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let id = Uuid::new_v4();
+
+        let cursor = encode_cursor(subscribed_at, id);
+
+        assert_eq!(decode_cursor(&cursor), Some((subscribed_at, id)));
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        for cursor in ["", "1700000000", "abc_def", "1700000000_not-a-uuid"] {
+            assert_eq!(decode_cursor(cursor), None);
+        }
+    }
+
+    #[test]
+    fn like_wildcards_are_matched_literally() {
+        assert_eq!(like_pattern("100%_\\"), "%100\\%\\_\\\\%");
+    }
+}
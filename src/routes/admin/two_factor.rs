@@ -0,0 +1,147 @@
+use axum::{extract::State, http::StatusCode, Form, Json};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::{
+        complete_second_factor, confirm_totp_enrollment, get_session_user,
+        record_failed_second_factor, start_totp_enrollment, verify_second_factor, AuthError,
+        AuthenticatedUser, SESSION_COOKIE,
+    },
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct CodeData {
+    code: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct EnrollmentResponse {
+    secret: String,
+    otpauth_uri: String,
+    qr_code_svg: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+/// Second login step, for users with two-factor authentication enabled.
+///
+/// Accepts either a TOTP code or a recovery code.
+#[tracing::instrument(name = "Admin login second factor", skip(database, jar, data))]
+pub async fn login_two_factor(
+    State(AppState { database, .. }): State<AppState>,
+    jar: CookieJar,
+    Form(data): Form<CodeData>,
+) -> StatusCode {
+    let Some(session_id) = jar.get(SESSION_COOKIE) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+
+    let Ok(session_user) = get_session_user(&database, session_id.value()).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let Some(session_user) = session_user.filter(|s| s.second_factor_pending) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+
+    match verify_second_factor(&database, session_user.user_id, &data.code).await {
+        Ok(()) => {}
+        Err(AuthError::InvalidCredentials) => {
+            if record_failed_second_factor(&database, session_id.value())
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR;
+            }
+            return StatusCode::UNAUTHORIZED;
+        }
+        Err(AuthError::Unexpected(e)) => {
+            tracing::error!("Failed to verify second factor: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+
+    if complete_second_factor(&database, session_id.value())
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+#[tracing::instrument(
+    name = "Start two-factor enrollment",
+    skip(database, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn start_two_factor_enrollment(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<EnrollmentResponse>, StatusCode> {
+    match start_totp_enrollment(&database, user.user_id, &user.username).await {
+        Ok(enrollment) => Ok(Json(EnrollmentResponse {
+            secret: enrollment.secret,
+            otpauth_uri: enrollment.otpauth_uri,
+            qr_code_svg: enrollment.qr_code_svg,
+        })),
+        Err(e) => {
+            tracing::error!("Failed to start TOTP enrollment: {:?}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Confirm two-factor enrollment",
+    skip(database, user, request_id, data),
+    fields(user_id = %user.user_id)
+)]
+pub async fn confirm_two_factor_enrollment(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Form(data): Form<CodeData>,
+) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+    let Ok(mut transaction) = database.begin().await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let code = data.code.trim();
+    let recovery_codes =
+        match confirm_totp_enrollment(&mut transaction, user.user_id, &user.username, code).await {
+            Ok(recovery_codes) => recovery_codes,
+            Err(AuthError::InvalidCredentials) => return Err(StatusCode::BAD_REQUEST),
+            Err(AuthError::Unexpected(e)) => {
+                tracing::error!("Failed to confirm TOTP enrollment: {}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "user.two_factor_enroll",
+        target_type: "user",
+        target_id: user.user_id.to_string(),
+        before: None,
+        after: None,
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if transaction.commit().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
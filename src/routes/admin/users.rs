@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    authentication::{compute_password_hash, validate_new_password, AuthenticatedUser},
+    authorization::Role,
+    domain::SubscriberEmail,
+    startup::AppState,
+};
+
+#[derive(serde::Serialize)]
+pub struct UserResponse {
+    user_id: Uuid,
+    username: String,
+    email: Option<String>,
+    role: Role,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewUserData {
+    username: String,
+    /// Where password reset links get sent.
+    email: Option<String>,
+    password: SecretString,
+    role: Role,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleData {
+    role: Role,
+}
+
+#[tracing::instrument(name = "List admin users", skip(database))]
+pub async fn list_users(
+    State(AppState { database, .. }): State<AppState>,
+) -> Result<Json<Vec<UserResponse>>, StatusCode> {
+    let rows =
+        sqlx::query!(r#"SELECT user_id, username, email, role FROM users ORDER BY username"#)
+            .fetch_all(&database)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+    let mut users = Vec::with_capacity(rows.len());
+    for row in rows {
+        let role = Role::try_from(row.role).map_err(|e| {
+            tracing::error!("Invalid role stored for user {}: {}", row.user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        users.push(UserResponse {
+            user_id: row.user_id,
+            username: row.username,
+            email: row.email,
+            role,
+        });
+    }
+
+    Ok(Json(users))
+}
+
+#[tracing::instrument(
+    name = "Create an admin user",
+    skip(database, user, request_id, data),
+    fields(username = %data.username, role = ?data.role)
+)]
+pub async fn create_user(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Json(data): Json<NewUserData>,
+) -> Result<(StatusCode, Json<UserResponse>), StatusCode> {
+    if data.username.trim().is_empty() || validate_new_password(&data.password).is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let email = match data.email.map(SubscriberEmail::parse).transpose() {
+        Ok(email) => email,
+        Err(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let password_hash = tokio::task::spawn_blocking(move || compute_password_hash(data.password))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            tracing::error!("Failed to hash password: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let Ok(mut transaction) = database.begin().await else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let user_id = Uuid::new_v4();
+    let result = sqlx::query!(
+        r#"INSERT INTO users (user_id, username, email, password_hash, role)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        user_id,
+        data.username,
+        email.as_ref().map(|e| e.as_ref()),
+        password_hash.expose_secret(),
+        data.role.as_str(),
+    )
+    .execute(&mut *transaction)
+    .await;
+
+    match result {
+        Ok(_) => {}
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Err(StatusCode::CONFLICT)
+        }
+        Err(e) => {
+            tracing::error!("Failed to execute query: {:?}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let created = UserResponse {
+        user_id,
+        username: data.username,
+        email: email.map(|e| e.to_string()),
+        role: data.role,
+    };
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "user.create",
+        target_type: "user",
+        target_id: user_id.to_string(),
+        before: None,
+        after: serde_json::to_value(&created).ok(),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if transaction.commit().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// Change the role of a user.
+///
+/// Demoting the last remaining owner is refused with a 409, otherwise
+/// nobody would be left to manage the team.
+#[tracing::instrument(
+    name = "Change the role of an admin user",
+    skip(database, user, request_id, data),
+    fields(role = ?data.role)
+)]
+pub async fn change_user_role(
+    State(AppState { database, .. }): State<AppState>,
+    user: AuthenticatedUser,
+    request_id: RequestId,
+    Path(user_id): Path<Uuid>,
+    Json(data): Json<RoleData>,
+) -> StatusCode {
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let Ok(owners) = lock_owners(&mut transaction).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    if data.role != Role::Owner && owners == [user_id] {
+        tracing::warn!(demoted_by = %user.user_id, "refusing to demote the last owner");
+        return StatusCode::CONFLICT;
+    }
+
+    let previous_role = sqlx::query_scalar!(
+        r#"SELECT role FROM users WHERE user_id = $1 FOR UPDATE"#,
+        user_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await;
+
+    let previous_role = match previous_role {
+        Ok(Some(previous_role)) => previous_role,
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Failed to execute query: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET role = $1 WHERE user_id = $2"#,
+        data.role.as_str(),
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Failed to execute query: {:?}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    let entry = AuditEntry {
+        actor: Actor::User(user.user_id),
+        action: "user.role_change",
+        target_type: "user",
+        target_id: user_id.to_string(),
+        before: Some(serde_json::json!({ "role": previous_role })),
+        after: Some(serde_json::json!({ "role": data.role })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/// Lock every owner row until the end of the transaction, so that two
+/// concurrent demotions cannot both observe a second owner.
+async fn lock_owners(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT user_id FROM users WHERE role = 'owner' FOR UPDATE"#)
+        .fetch_all(&mut **transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })
+}
@@ -0,0 +1,9 @@
+use askama::Template;
+use axum::{http::StatusCode, response::Html};
+
+pub fn render(template: impl Template) -> Result<Html<String>, StatusCode> {
+    template.render().map(Html).map_err(|e| {
+        tracing::error!("Failed to render template: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
@@ -1,7 +1,18 @@
+mod admin;
 mod health_check;
+mod html;
+mod preferences;
 mod subscriptions;
+mod subscriptions_challenge;
 mod subscriptions_confirm;
+mod subscriptions_data_export;
+mod subscriptions_locale;
 
+pub use admin::*;
 pub use health_check::*;
+pub use preferences::*;
 pub use subscriptions::*;
+pub use subscriptions_challenge::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data_export::*;
+pub use subscriptions_locale::*;
@@ -0,0 +1,325 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::Form;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    domain::SubscriberName,
+    lists::{list_topics, Topic},
+    routes::html::render,
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    subscription_token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesData {
+    subscription_token: String,
+    name: String,
+    /// The slugs of the topics the subscriber wants to receive.
+    #[serde(default)]
+    topics: Vec<String>,
+    #[serde(default)]
+    paused: bool,
+}
+
+struct TopicChoice {
+    slug: String,
+    name: String,
+    receiving: bool,
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions/preferences.html")]
+struct PreferencesTemplate {
+    subscription_token: String,
+    list_name: String,
+    name: String,
+    topics: Vec<TopicChoice>,
+    paused: bool,
+    saved: bool,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions/invalid_token.html")]
+struct InvalidTokenTemplate;
+
+/// The subscriber a preferences link belongs to, with what they chose.
+struct Preferences {
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    list_slug: String,
+    list_name: String,
+    name: String,
+    paused: bool,
+    topics: Vec<Topic>,
+    /// The slugs of the topics of the list the subscriber turned off.
+    opted_out: Vec<String>,
+}
+
+impl Preferences {
+    fn page(&self, subscription_token: String) -> PreferencesTemplate {
+        PreferencesTemplate {
+            subscription_token,
+            list_name: self.list_name.clone(),
+            name: self.name.clone(),
+            topics: self
+                .topics
+                .iter()
+                .map(|topic| TopicChoice {
+                    slug: topic.slug.clone(),
+                    name: topic.name.clone(),
+                    receiving: !self.opted_out.contains(&topic.slug),
+                })
+                .collect(),
+            paused: self.paused,
+            saved: false,
+            error: None,
+        }
+    }
+}
+
+fn invalid_token() -> Response {
+    (StatusCode::UNAUTHORIZED, render(InvalidTokenTemplate)).into_response()
+}
+
+/// The preference centre, where subscribers choose the topics they receive,
+/// change their name and pause delivery.
+///
+/// The subscription token from the confirmation email identifies the
+/// subscriber and the list, once the subscription is confirmed. Topics are
+/// those of that list, while the name and the pause apply to every list of
+/// the subscriber.
+#[tracing::instrument(name = "Show the preference centre", skip(database, params))]
+pub async fn preferences_page(
+    State(AppState { database, .. }): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+) -> Response {
+    match get_preferences(&database, &params.subscription_token).await {
+        Ok(Some(preferences)) => {
+            render(preferences.page(params.subscription_token)).into_response()
+        }
+        Ok(None) => invalid_token(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Save the choices made in the preference centre.
+#[tracing::instrument(
+    name = "Update subscriber preferences",
+    skip(database, request_id, data)
+)]
+pub async fn update_preferences(
+    State(AppState { database, .. }): State<AppState>,
+    request_id: RequestId,
+    Form(data): Form<PreferencesData>,
+) -> Response {
+    let preferences = match get_preferences(&database, &data.subscription_token).await {
+        Ok(Some(preferences)) => preferences,
+        Ok(None) => return invalid_token(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let name = match SubscriberName::parse(data.name) {
+        Ok(name) => name,
+        Err(error) => {
+            let page = PreferencesTemplate {
+                error: Some(error.to_string()),
+                ..preferences.page(data.subscription_token)
+            };
+            return (StatusCode::BAD_REQUEST, render(page)).into_response();
+        }
+    };
+
+    let opted_out: Vec<&Topic> = preferences
+        .topics
+        .iter()
+        .filter(|topic| !data.topics.contains(&topic.slug))
+        .collect();
+    let opted_out_slugs: Vec<String> = opted_out.iter().map(|topic| topic.slug.clone()).collect();
+
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if save_preferences(
+        &mut transaction,
+        &preferences,
+        &name,
+        data.paused,
+        &opted_out,
+    )
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // Names are personal data and stay out of the audit log.
+    let entry = AuditEntry {
+        actor: Actor::Subscriber(preferences.subscriber_id),
+        action: "subscriber.preferences_change",
+        target_type: "subscriber",
+        target_id: preferences.subscriber_id.to_string(),
+        before: Some(serde_json::json!({
+            "list": preferences.list_slug,
+            "paused": preferences.paused,
+            "opted_out_topics": preferences.opted_out,
+        })),
+        after: Some(serde_json::json!({
+            "list": preferences.list_slug,
+            "paused": data.paused,
+            "opted_out_topics": opted_out_slugs,
+            "name_changed": name.as_ref() != preferences.name,
+        })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let preferences = Preferences {
+        name: name.as_ref().to_owned(),
+        paused: data.paused,
+        opted_out: opted_out_slugs,
+        ..preferences
+    };
+    let page = PreferencesTemplate {
+        saved: true,
+        ..preferences.page(data.subscription_token)
+    };
+    render(page).into_response()
+}
+
+#[tracing::instrument(name = "Get preferences by token", skip(database, subscription_token))]
+async fn get_preferences(
+    database: &PgPool,
+    subscription_token: &str,
+) -> Result<Option<Preferences>, sqlx::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT s.id, s.name, s.delivery_paused_at, l.list_id, l.slug, l.name AS list_name
+        FROM subscription_tokens t
+        JOIN list_memberships m ON m.list_id = t.list_id AND m.subscriber_id = t.subscriber_id
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        JOIN lists l ON l.list_id = t.list_id
+        WHERE t.subscription_token = $1 AND m.status = 'confirmed'"#,
+        subscription_token,
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    let Some(subscriber) = subscriber else {
+        return Ok(None);
+    };
+
+    let topics = list_topics(database, subscriber.list_id).await?;
+    let opted_out = sqlx::query_scalar!(
+        r#"SELECT t.slug FROM topic_opt_outs o
+        JOIN topics t ON t.topic_id = o.topic_id
+        WHERE o.subscriber_id = $1 AND t.list_id = $2"#,
+        subscriber.id,
+        subscriber.list_id,
+    )
+    .fetch_all(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(Some(Preferences {
+        subscriber_id: subscriber.id,
+        list_id: subscriber.list_id,
+        list_slug: subscriber.slug,
+        list_name: subscriber.list_name,
+        name: subscriber.name,
+        paused: subscriber.delivery_paused_at.is_some(),
+        topics,
+        opted_out,
+    }))
+}
+
+/// Save the subscriber's name, whether delivery is paused and the topics of
+/// the list they turned off.
+#[tracing::instrument(
+    name = "Save subscriber preferences",
+    skip(transaction, preferences, name, opted_out)
+)]
+async fn save_preferences(
+    transaction: &mut Transaction<'_, Postgres>,
+    preferences: &Preferences,
+    name: &SubscriberName,
+    paused: bool,
+    opted_out: &[&Topic],
+) -> Result<(), sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+    let opted_out: Vec<Uuid> = opted_out.iter().map(|topic| topic.topic_id).collect();
+    sqlx::query!(
+        r#"UPDATE subscriptions
+        SET name = $2,
+            delivery_paused_at = CASE WHEN $3 THEN COALESCE(delivery_paused_at, $4) END
+        WHERE id = $1"#,
+        preferences.subscriber_id,
+        name.as_ref(),
+        paused,
+        now,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"DELETE FROM topic_opt_outs o
+        USING topics t
+        WHERE t.topic_id = o.topic_id AND o.subscriber_id = $1 AND t.list_id = $2
+        AND NOT (o.topic_id = ANY($3))"#,
+        preferences.subscriber_id,
+        preferences.list_id,
+        &opted_out,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"INSERT INTO topic_opt_outs (subscriber_id, topic_id, opted_out_at)
+        SELECT $1, topic_id, $3 FROM UNNEST($2::uuid[]) AS t(topic_id)
+        ON CONFLICT (subscriber_id, topic_id) DO NOTHING"#,
+        preferences.subscriber_id,
+        &opted_out,
+        now,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
@@ -1,79 +1,226 @@
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::Form;
+use axum::response::{IntoResponse, Response};
+use axum::{Form, Json};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use sqlx::{Executor, Postgres, Transaction};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::{domain::{NewSubscriber, SubscriberEmail, SubscriberName}, email_client::EmailClient, startup::AppState};
+use crate::{audit::{record_audit_entry, Actor, AuditEntry, RequestId}, client::ClientInfo, configuration::DeliverabilityMode, consent::{record_consent, ConsentEvent}, deliverability::Deliverability, domain::{NewSubscriber, SubscriberEmail, SubscriberName, ValidationError}, email_client::EmailClient, email_policy::{flag_subscriber, Verdict}, email_templates::{EmailError, EmailTemplate, EmailTemplates}, erasure::is_suppressed, lists::{get_default_list, get_list, join_list, List}, signup_challenge::SignupChallenge, startup::AppState};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     email: String,
     name: String,
+    /// The form or page the subscription comes from.
+    source: Option<String>,
+    /// The version of the consent text shown next to the form.
+    consent_text_version: Option<String>,
+    /// The language to write to the subscriber in, instead of the one
+    /// preferred by their browser.
+    locale: Option<String>,
+    /// A honeypot: the form hides it from people, so anything in it was
+    /// filled in by a bot.
+    website: Option<String>,
+    /// The token from `GET /subscriptions/challenge`, when signups are
+    /// challenged.
+    challenge: Option<String>,
+    /// The proof of work found for the challenge, when one is required.
+    proof_of_work: Option<String>,
 }
 
+/// Every field that was refused, not only the first one.
 impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+    type Error = Vec<ValidationError>;
 
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { name, email })
+        match (SubscriberName::parse(value.name), SubscriberEmail::parse(value.email)) {
+            (Ok(name), Ok(email)) => Ok(Self { name, email }),
+            (name, email) => Err(name.err().into_iter().chain(email.err()).collect()),
+        }
     }
 }
 
+/// The `400 Bad Request` listing why the submitted fields were refused.
+fn invalid_fields(errors: Vec<ValidationError>) -> Response {
+    (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "errors": errors }))).into_response()
+}
+
+/// Subscribe to the default list.
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(data, database, email_client),
+    skip(state, data, request_id, client),
     fields(
         subscriber_email = %data.email,
         subscriber_name = %data.name,
     )
 )]
 pub async fn subscribe(
-    State(AppState { database, email_client, base_url, .. }): State<AppState>,
-    Form(data): Form<FormData>) -> StatusCode {
-    let new_subscriber = match data.try_into() {
+    State(state): State<AppState>,
+    request_id: RequestId,
+    client: ClientInfo,
+    Form(data): Form<FormData>) -> Response {
+    let Ok(list) = get_default_list(&state.database).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    subscribe_to(state, list, request_id, client, data).await
+}
+
+/// Subscribe to the list named by the slug.
+#[tracing::instrument(
+    name = "Adding a new subscriber to a list",
+    skip(state, data, request_id, client),
+    fields(
+        subscriber_email = %data.email,
+        subscriber_name = %data.name,
+    )
+)]
+pub async fn subscribe_to_list(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    request_id: RequestId,
+    client: ClientInfo,
+    Form(data): Form<FormData>) -> Response {
+    let list = match get_list(&state.database, &slug).await {
+        Ok(Some(list)) => list,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    subscribe_to(state, list, request_id, client, data).await
+}
+
+async fn subscribe_to(
+    AppState { database, email_client, email_templates, base_url, suppression_salt, signup_challenge, email_policy, deliverability, email_normalisation, .. }: AppState,
+    list: List,
+    request_id: RequestId,
+    client: ClientInfo,
+    mut data: FormData) -> Response {
+    // Bots are told they subscribed, so that they learn nothing from the answer.
+    if let Err(reason) = check_for_bot(&data, &signup_challenge) {
+        tracing::warn!(reason, "dropped a suspicious signup");
+        return StatusCode::OK.into_response();
+    }
+
+    let consent = ConsentEvent::Subscribe {
+        source: data.source.take(),
+        consent_text_version: data.consent_text_version.take(),
+    };
+    let locale = email_templates.negotiate_locale(data.locale.as_deref(), client.accept_language.as_deref());
+    let mut new_subscriber: NewSubscriber = match data.try_into() {
         Ok(subscriber) => subscriber,
-        Err(_) => return StatusCode::BAD_REQUEST,
+        Err(errors) => return invalid_fields(errors),
+    };
+    new_subscriber.email = email_normalisation.apply(new_subscriber.email);
+
+    let verdict = match email_policy.check(&database, &new_subscriber.email).await {
+        Ok(Verdict::Reject(finding)) => {
+            tracing::info!(reason = finding.as_str(), "signup refused by the email policy");
+            return invalid_fields(vec![ValidationError::EmailNotAccepted]);
+        }
+        Ok(verdict) => verdict,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
+    if let Deliverability::Undeliverable { suggestion } = deliverability.check(&new_subscriber.email).await {
+        match deliverability.mode() {
+            DeliverabilityMode::Hard => {
+                tracing::info!("signup refused because its domain cannot receive email");
+                return invalid_fields(vec![ValidationError::UndeliverableEmail(suggestion)]);
+            }
+            _ => tracing::warn!("accepted a signup whose domain does not seem to receive email"),
+        }
+    }
+
+    // Erased addresses are turned away without saying so, like any other
+    // answer that would reveal who was on the list.
+    match is_suppressed(&database, &suppression_salt, &new_subscriber.email).await {
+        Ok(false) => {}
+        Ok(true) => return StatusCode::OK.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
     let Ok(mut transaction) = database.begin().await else {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
-    let Ok(subscriber_id) = insert_subscriber(&mut transaction, &new_subscriber).await else {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+    let Ok(subscriber_id) = insert_subscriber(&mut transaction, &new_subscriber, locale.as_deref()).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
+    match join_list(&mut transaction, list.list_id, subscriber_id).await {
+        Ok(true) => {}
+        // Already confirmed: answer as if they were new, to not reveal who is subscribed.
+        Ok(false) => return StatusCode::OK.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    if let Verdict::Flag(finding) = verdict {
+        if flag_subscriber(&mut *transaction, subscriber_id, finding).await.is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
     let subscription_token = generate_random_subscription_token();
-    if store_token(&mut transaction, subscriber_id, &subscription_token).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+    if store_token(&mut transaction, subscriber_id, list.list_id, &subscription_token).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if record_consent(&mut *transaction, subscriber_id, list.list_id, &client, consent).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let entry = AuditEntry {
+        actor: Actor::Subscriber(subscriber_id),
+        action: "subscription.create",
+        target_type: "subscriber",
+        target_id: subscriber_id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({ "status": "pending_confirmation", "list": list.slug })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
     if transaction.commit().await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
-    if send_confirmation_email(&email_client, new_subscriber, base_url, &subscription_token).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+    if send_confirmation_email(&email_client, &email_templates, new_subscriber, locale.as_deref(), base_url, &subscription_token).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
-    StatusCode::OK
+    StatusCode::OK.into_response()
+}
+
+/// Why the signup looks like it was submitted by a bot, if it does.
+fn check_for_bot(data: &FormData, signup_challenge: &SignupChallenge) -> Result<(), String> {
+    if data.website.as_deref().is_some_and(|website| !website.is_empty()) {
+        return Err("honeypot filled in".into());
+    }
+    if signup_challenge.is_enabled() {
+        // Invalid addresses are refused afterwards, with the reason why.
+        let email = SubscriberEmail::parse(data.email.clone())
+            .map_or_else(|_| data.email.clone(), |email| email.normalised().to_owned());
+        signup_challenge
+            .verify(data.challenge.as_deref(), data.proof_of_work.as_deref(), &email, OffsetDateTime::now_utc())
+            .map_err(|failure| format!("challenge failed: {:?}", failure))?;
+    }
+    Ok(())
 }
 
 pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Uuid,
     subscription_token: &str
 ) -> Result<(), sqlx::Error> {
     let query = sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        VALUES ($1, $2, $3)"#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        list_id
     );
     transaction.execute(query) .await .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
@@ -84,28 +231,38 @@ pub async fn store_token(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber)
+    skip(email_client, email_templates, new_subscriber)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
+    email_templates: &EmailTemplates,
     new_subscriber: NewSubscriber,
+    locale: Option<&str>,
     base_url: String,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), EmailError> {
     let confirmation_link = format!("{}/subscriptions/confirm?subscription_token={}", base_url, subscription_token);
 
-    let plain_body = format!("Welcome to our newsletter!\nVisit {} to confirm your subscription.", confirmation_link);
-    let html_body = format!("Welcome to our newsletter!<bt />\
-            Click <a href=\"{}\">here</a> to confirm your subscription.", confirmation_link);
+    let email = email_templates.render(
+        EmailTemplate::Confirmation,
+        locale,
+        minijinja::context! { name => new_subscriber.name.as_ref(), confirmation_link },
+    )?;
 
     email_client.send_email(
         new_subscriber.email,
-        "Welcome!",
-        &html_body,
-        &plain_body
-    ).await
+        &email.subject,
+        &email.html,
+        &email.text
+    ).await?;
+    Ok(())
 }
 
+/// Save a new subscriber, returning their id.
+///
+/// Subscribing again before confirming any list starts over with the new
+/// details, for instance after the confirmation link expired. Those of a
+/// confirmed subscriber are kept: they only join another list.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(transaction, new_subscriber)
@@ -113,27 +270,36 @@ pub async fn send_confirmation_email(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
+    locale: Option<&str>,
 ) -> Result<Uuid, sqlx::Error> {
-    let id = Uuid::new_v4();
-    let query = sqlx::query!(
+    sqlx::query_scalar!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')"#,
-        id,
+        INSERT INTO subscriptions (id, email, normalised_email, name, subscribed_at, status, locale)
+        VALUES ($1, $2, $3, $4, $5, 'pending_confirmation', $6)
+        ON CONFLICT (normalised_email) DO UPDATE
+        SET (email, name, subscribed_at, locale) = (
+            CASE WHEN subscriptions.status = 'pending_confirmation' THEN EXCLUDED.email ELSE subscriptions.email END,
+            CASE WHEN subscriptions.status = 'pending_confirmation' THEN EXCLUDED.name ELSE subscriptions.name END,
+            CASE WHEN subscriptions.status = 'pending_confirmation' THEN EXCLUDED.subscribed_at ELSE subscriptions.subscribed_at END,
+            CASE WHEN subscriptions.status = 'pending_confirmation' THEN EXCLUDED.locale ELSE subscriptions.locale END
+        )
+        RETURNING id"#,
+        Uuid::new_v4(),
         new_subscriber.email.as_ref(),
+        new_subscriber.email.normalised(),
         new_subscriber.name.as_ref(),
-        OffsetDateTime::now_utc()
-    );
-    transaction.execute(query)
+        OffsetDateTime::now_utc(),
+        locale
+    )
+        .fetch_one(&mut **transaction)
         .await
         .map_err(|e| {
             tracing::error!("failed to execute query: {:?}", e);
             e
-        })?;
-    Ok(id)
+        })
 }
 
-fn generate_random_subscription_token() -> String {
+pub fn generate_random_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -0,0 +1,19 @@
+use axum::{extract::State, http::StatusCode, Json};
+use time::OffsetDateTime;
+
+use crate::{signup_challenge::IssuedChallenge, startup::AppState};
+
+/// Hand out a challenge for a signup form to submit with the signup.
+///
+/// There is nothing to fetch when signups are not challenged.
+#[tracing::instrument(name = "Issue a signup challenge", skip(signup_challenge))]
+pub async fn issue_signup_challenge(
+    State(AppState {
+        signup_challenge, ..
+    }): State<AppState>,
+) -> Result<Json<IssuedChallenge>, StatusCode> {
+    if !signup_challenge.is_enabled() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(signup_challenge.issue(OffsetDateTime::now_utc())))
+}
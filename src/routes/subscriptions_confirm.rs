@@ -1,43 +1,188 @@
-use axum::{extract::{Query, State}, http::StatusCode};
-use sqlx::PgPool;
+use askama::Template;
+use axum::{extract::{Query, State}, http::StatusCode, response::{IntoResponse, Redirect, Response}, Form};
+use sqlx::{PgPool, Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
-use crate::startup::AppState;
+use crate::{audit::{record_audit_entry, Actor, AuditEntry, RequestId}, client::ClientInfo, configuration::ConfirmationSettings, consent::{record_consent, ConsentEvent}, lists::confirm_membership, routes::html::render, startup::AppState};
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     subscription_token: String
 }
 
+#[derive(Template)]
+#[template(path = "subscriptions/confirm.html")]
+struct ConfirmationTemplate {
+    subscription_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions/confirmed.html")]
+struct ConfirmedTemplate {
+    subscription_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions/already_confirmed.html")]
+struct AlreadyConfirmedTemplate;
+
+#[derive(Template)]
+#[template(path = "subscriptions/invalid_token.html")]
+struct InvalidTokenTemplate;
+
+#[derive(Template)]
+#[template(path = "subscriptions/expired_token.html")]
+struct ExpiredTokenTemplate;
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    list_slug: String,
+    created_at: OffsetDateTime,
+    /// The status of the subscriber on the list of the token.
+    status: String,
+}
+
+/// How following a confirmation link ended, as shown to the subscriber.
+enum Outcome {
+    /// Confirmed with the token, which now opens the preference centre.
+    Confirmed(String),
+    AlreadyConfirmed,
+    InvalidToken,
+    ExpiredToken,
+}
+
+impl Outcome {
+    /// Our own page for the outcome, or the redirect configured for it.
+    fn into_response(self, settings: &ConfirmationSettings) -> Response {
+        let redirects = &settings.redirects;
+        let (redirect, status, page) = match self {
+            Outcome::Confirmed(subscription_token) => (&redirects.confirmed, StatusCode::OK, render(ConfirmedTemplate { subscription_token })),
+            Outcome::AlreadyConfirmed => (&redirects.already_confirmed, StatusCode::OK, render(AlreadyConfirmedTemplate)),
+            Outcome::InvalidToken => (&redirects.invalid, StatusCode::UNAUTHORIZED, render(InvalidTokenTemplate)),
+            Outcome::ExpiredToken => (&redirects.expired, StatusCode::GONE, render(ExpiredTokenTemplate)),
+        };
+
+        if let Some(url) = redirect {
+            return Redirect::to(url).into_response();
+        }
+        (status, page).into_response()
+    }
+}
+
+/// The token, if the subscriber can still confirm with it.
+fn check_token(token: Option<SubscriptionToken>, settings: &ConfirmationSettings) -> Result<SubscriptionToken, Outcome> {
+    match token {
+        None => Err(Outcome::InvalidToken),
+        Some(token) if token.status == "confirmed" => Err(Outcome::AlreadyConfirmed),
+        Some(token) if OffsetDateTime::now_utc() - token.created_at > settings.token_lifetime() => Err(Outcome::ExpiredToken),
+        Some(token) => Ok(token),
+    }
+}
+
+/// The page the confirmation link lands on, asking to confirm with a button.
+///
+/// Link scanners follow the links in incoming emails, so following the link
+/// must not confirm anything by itself.
+#[tracing::instrument(
+    name = "Show the confirmation page",
+    skip(database, confirmation, params)
+)]
+pub async fn confirmation_page(
+    State(AppState { database, confirmation, .. }): State<AppState>,
+    Query(params): Query<Parameters>) -> Response {
+    let Ok(token) = get_subscription_token(&database, &params.subscription_token).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if let Err(outcome) = check_token(token, &confirmation) {
+        return outcome.into_response(&confirmation);
+    }
+
+    render(ConfirmationTemplate { subscription_token: params.subscription_token }).into_response()
+}
+
+/// Confirm a pending subscriber on the list of the token, and the
+/// subscriber's address on their first confirmation. Confirming more than
+/// once changes nothing.
 #[tracing::instrument(
     name = "Confirm a pending subscriber",
-    skip(params)
+    skip(database, confirmation, params, request_id, client)
 )]
-pub async fn confirm(State(AppState { database, .. }): State<AppState>, Query(params): Query<Parameters>) -> StatusCode {
-    let Ok(id) = get_subscriber_id_from_token(&database, &params.subscription_token).await else {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+pub async fn confirm(
+    State(AppState { database, confirmation, .. }): State<AppState>,
+    request_id: RequestId,
+    client: ClientInfo,
+    Form(params): Form<Parameters>) -> Response {
+    let Ok(token) = get_subscription_token(&database, &params.subscription_token).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let SubscriptionToken { subscriber_id: id, list_id, list_slug, created_at, .. } = match check_token(token, &confirmation) {
+        Ok(token) => token,
+        Err(outcome) => return outcome.into_response(&confirmation),
     };
 
-    let Some(id) = id else {
-        return StatusCode::UNAUTHORIZED;
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
-    if confirm_subscriber(&database, id).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+    let Ok(previous_status) = confirm_membership(&mut transaction, list_id, id).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
-    StatusCode::OK
+    // Another request may have confirmed in the meantime.
+    if previous_status == "confirmed" {
+        return Outcome::AlreadyConfirmed.into_response(&confirmation);
+    }
+
+    if confirm_subscriber(&mut transaction, id).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let suspiciously_fast = confirmation.suspicious_within_seconds.is_some_and(|seconds| {
+        OffsetDateTime::now_utc() - created_at < Duration::seconds(seconds as i64)
+    });
+    if suspiciously_fast {
+        tracing::warn!(subscriber_id = %id, "subscription confirmed suspiciously soon after the email was sent");
+    }
+
+    let entry = AuditEntry {
+        actor: Actor::Subscriber(id),
+        action: "subscription.confirm",
+        target_type: "subscriber",
+        target_id: id.to_string(),
+        before: Some(serde_json::json!({ "status": previous_status, "list": list_slug })),
+        after: Some(serde_json::json!({ "status": "confirmed", "list": list_slug })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let consent = ConsentEvent::Confirm { suspiciously_fast };
+    if record_consent(&mut *transaction, id, list_id, &client, consent).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    Outcome::Confirmed(params.subscription_token).into_response(&confirmation)
 }
 
 #[tracing::instrument(
-    name = "Get subscriber_id from token",
+    name = "Get subscription token",
     skip(database, subscription_token)
 )]
-async fn get_subscriber_id_from_token(database: &PgPool, subscription_token: &str) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id
-           FROM subscription_tokens
-           WHERE subscription_token = $1
+async fn get_subscription_token(database: &PgPool, subscription_token: &str) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"SELECT t.subscriber_id, t.list_id, l.slug AS list_slug, t.created_at, m.status
+           FROM subscription_tokens t
+           JOIN list_memberships m ON m.list_id = t.list_id AND m.subscriber_id = t.subscriber_id
+           JOIN lists l ON l.list_id = t.list_id
+           WHERE t.subscription_token = $1
         "#,
         subscription_token
     )
@@ -46,21 +191,22 @@ async fn get_subscriber_id_from_token(database: &PgPool, subscription_token: &st
         .map_err(|e| {
             tracing::error!("Failed to execute query: {:?}", e);
             e
-        })?;
-
-    Ok(result.map(|r| r.subscriber_id))
+        })
 }
 
+/// Mark a subscriber's address as confirmed.
 #[tracing::instrument(
     name = "Mark subscriber as confirmed",
-    skip(database, id)
+    skip(transaction, id)
 )]
-async fn confirm_subscriber(database: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+async fn confirm_subscriber(transaction: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<(), sqlx::Error> {
     sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+        r#"UPDATE subscriptions
+        SET status = 'confirmed', confirmed_at = COALESCE(confirmed_at, now())
+        WHERE id = $1"#,
         id
     )
-        .execute(database)
+        .execute(&mut **transaction)
         .await
         .map_err(|e| {
             tracing::error!("Failed to execute query: {:?}", e);
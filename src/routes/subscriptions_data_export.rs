@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Form,
+};
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    data_export::{
+        compile_subscriber_archive, get_subscriber_id_from_export_token, issue_data_export_token,
+        SubscriberArchive,
+    },
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    email_templates::{EmailError, EmailTemplate, EmailTemplates},
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct DataExportRequestData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataExportParameters {
+    token: String,
+}
+
+/// Email a download link for their data to a subscriber.
+///
+/// The answer is the same whether or not the address is subscribed, so the
+/// endpoint cannot be used to find out who is on the list.
+#[tracing::instrument(
+    name = "Request a subscriber data export",
+    skip(
+        database,
+        email_client,
+        email_templates,
+        base_url,
+        email_normalisation,
+        data
+    )
+)]
+pub async fn request_data_export(
+    State(AppState {
+        database,
+        email_client,
+        email_templates,
+        base_url,
+        email_normalisation,
+        ..
+    }): State<AppState>,
+    Form(data): Form<DataExportRequestData>,
+) -> StatusCode {
+    let Ok(email) = SubscriberEmail::parse(data.email) else {
+        return StatusCode::OK;
+    };
+    let email = email_normalisation.apply(email);
+
+    let issued = match issue_data_export_token(&database, email.normalised()).await {
+        Ok(Some(issued)) => issued,
+        Ok(None) => return StatusCode::OK,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    tracing::info!(subscriber_id = %issued.subscriber_id, "issued a data export token");
+    tokio::spawn(async move {
+        if let Err(e) = send_data_export_email(
+            &email_client,
+            &email_templates,
+            email,
+            issued.locale.as_deref(),
+            &base_url,
+            &issued.token,
+        )
+        .await
+        {
+            tracing::error!("Failed to send data export email: {:?}", e);
+        }
+    });
+
+    StatusCode::OK
+}
+
+#[tracing::instrument(
+    name = "Send a data export email",
+    skip(email_client, email_templates, recipient, token)
+)]
+async fn send_data_export_email(
+    email_client: &EmailClient,
+    email_templates: &EmailTemplates,
+    recipient: SubscriberEmail,
+    locale: Option<&str>,
+    base_url: &str,
+    token: &str,
+) -> Result<(), EmailError> {
+    let export_link = format!("{}/subscriptions/data-export?token={}", base_url, token);
+
+    let email = email_templates.render(
+        EmailTemplate::DataExport,
+        locale,
+        minijinja::context! { export_link },
+    )?;
+
+    email_client
+        .send_email(recipient, &email.subject, &email.html, &email.text)
+        .await?;
+    Ok(())
+}
+
+/// Download the data held about the subscriber the link was sent to.
+#[tracing::instrument(
+    name = "Download a subscriber data export",
+    skip(database, request_id, params)
+)]
+pub async fn download_data_export(
+    State(AppState { database, .. }): State<AppState>,
+    request_id: RequestId,
+    Query(params): Query<DataExportParameters>,
+) -> Result<SubscriberArchive, StatusCode> {
+    let subscriber_id = get_subscriber_id_from_export_token(&database, &params.token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let archive = compile_subscriber_archive(&database, subscriber_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let entry = AuditEntry {
+        actor: Actor::Subscriber(subscriber_id),
+        action: "subscriber.data_export",
+        target_type: "subscriber",
+        target_id: subscriber_id.to_string(),
+        before: None,
+        after: None,
+    };
+    record_audit_entry(&database, &request_id, entry)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(archive)
+}
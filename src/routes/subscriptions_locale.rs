@@ -0,0 +1,112 @@
+use axum::{extract::State, http::StatusCode, Form};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_entry, Actor, AuditEntry, RequestId},
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct LocaleChangeData {
+    subscription_token: String,
+    locale: String,
+}
+
+/// Change the language a subscriber's emails are written in.
+///
+/// The subscription token from the confirmation email identifies the
+/// subscriber; it stays valid after confirming.
+#[tracing::instrument(
+    name = "Change a subscriber's locale",
+    skip(database, email_templates, request_id, data)
+)]
+pub async fn change_locale(
+    State(AppState {
+        database,
+        email_templates,
+        ..
+    }): State<AppState>,
+    request_id: RequestId,
+    Form(data): Form<LocaleChangeData>,
+) -> StatusCode {
+    let Some(locale) = email_templates.supported_locale(&data.locale) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    let subscriber = match get_subscriber_locale(&database, &data.subscription_token).await {
+        Ok(Some(subscriber)) => subscriber,
+        Ok(None) => return StatusCode::UNAUTHORIZED,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    if subscriber.locale.as_deref() == Some(locale.as_str()) {
+        return StatusCode::OK;
+    }
+
+    let Ok(mut transaction) = database.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+
+    let updated = sqlx::query!(
+        r#"UPDATE subscriptions SET locale = $1 WHERE id = $2"#,
+        locale,
+        subscriber.id,
+    )
+    .execute(&mut *transaction)
+    .await;
+    if let Err(e) = updated {
+        tracing::error!("Failed to execute query: {:?}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    let entry = AuditEntry {
+        actor: Actor::Subscriber(subscriber.id),
+        action: "subscriber.locale_change",
+        target_type: "subscriber",
+        target_id: subscriber.id.to_string(),
+        before: Some(serde_json::json!({ "locale": subscriber.locale })),
+        after: Some(serde_json::json!({ "locale": locale })),
+    };
+    if record_audit_entry(&mut *transaction, &request_id, entry)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    if transaction.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+struct SubscriberLocale {
+    id: Uuid,
+    locale: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Get subscriber locale by token",
+    skip(database, subscription_token)
+)]
+async fn get_subscriber_locale(
+    database: &PgPool,
+    subscription_token: &str,
+) -> Result<Option<SubscriberLocale>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberLocale,
+        r#"SELECT s.id, s.locale
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1"#,
+        subscription_token
+    )
+    .fetch_optional(database)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
@@ -0,0 +1,285 @@
+//! Challenges that signup forms solve to show a person filled them in.
+//!
+//! The form fetches a challenge token when it is displayed and submits it
+//! with the signup. The token is signed and carries the time it was issued,
+//! so nothing is stored: a signup that comes back too soon, too late or
+//! without a valid token is taken for a bot's. The form can also be asked to
+//! spend some CPU time finding a proof of work for the token and the address
+//! being signed up, which is cheap once for a person and expensive for a bot
+//! signing up in bulk. As nothing is stored, a token can be answered more than
+//! once while it is valid, which is why tokens only last minutes.
+
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::{authentication::generate_token, configuration::SignupChallengeSettings};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug)]
+pub struct SignupChallenge {
+    settings: SignupChallengeSettings,
+}
+
+/// What the signup form needs to answer a challenge.
+#[derive(serde::Serialize)]
+pub struct IssuedChallenge {
+    pub token: String,
+    /// Set when the form must find a proof of work for the token.
+    pub proof_of_work_bits: Option<u32>,
+}
+
+/// Why a signup failed its challenge.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChallengeFailure {
+    Missing,
+    Malformed,
+    InvalidSignature,
+    TooFast,
+    Expired,
+    NoProofOfWork,
+}
+
+impl SignupChallenge {
+    pub fn new(settings: SignupChallengeSettings) -> Self {
+        Self { settings }
+    }
+
+    /// Whether signups have to answer a challenge at all.
+    pub fn is_enabled(&self) -> bool {
+        self.settings.is_enabled()
+    }
+
+    pub fn issue(&self, now: OffsetDateTime) -> IssuedChallenge {
+        let payload = format!("{}.{}", now.unix_timestamp(), generate_token(16));
+        let signature = hex(&self.sign(&payload));
+        IssuedChallenge {
+            token: format!("{}.{}", payload, signature),
+            proof_of_work_bits: self.settings.proof_of_work_bits,
+        }
+    }
+
+    /// Check the challenge token and proof of work submitted with a signup
+    /// of `email`, in its normalised form.
+    pub fn verify(
+        &self,
+        token: Option<&str>,
+        proof_of_work: Option<&str>,
+        email: &str,
+        now: OffsetDateTime,
+    ) -> Result<(), ChallengeFailure> {
+        let token = token
+            .filter(|token| !token.is_empty())
+            .ok_or(ChallengeFailure::Missing)?;
+        let (payload, signature) = token.rsplit_once('.').ok_or(ChallengeFailure::Malformed)?;
+        let signature = unhex(signature).ok_or(ChallengeFailure::Malformed)?;
+        let issued_at = payload
+            .split_once('.')
+            .and_then(|(issued_at, _)| issued_at.parse::<i64>().ok())
+            .ok_or(ChallengeFailure::Malformed)?;
+
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| ChallengeFailure::InvalidSignature)?;
+
+        let age = now.unix_timestamp() - issued_at;
+        if age > self.settings.max_age_seconds as i64 {
+            return Err(ChallengeFailure::Expired);
+        }
+        if let Some(min_fill_seconds) = self.settings.min_fill_seconds {
+            if age < min_fill_seconds as i64 {
+                return Err(ChallengeFailure::TooFast);
+            }
+        }
+        if let Some(bits) = self.settings.proof_of_work_bits {
+            let solved = proof_of_work.is_some_and(|solution| solves(token, email, solution, bits));
+            if !solved {
+                return Err(ChallengeFailure::NoProofOfWork);
+            }
+        }
+        Ok(())
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.settings.secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        self.mac(payload).finalize().into_bytes().to_vec()
+    }
+}
+
+/// Whether the SHA-256 hash of `{token}:{email}:{solution}` starts with at
+/// least `bits` zero bits.
+pub fn solves(token: &str, email: &str, solution: &str, bits: u32) -> bool {
+    let hash = Sha256::digest(format!("{}:{}:{}", token, email, solution).as_bytes());
+    leading_zero_bits(&hash) >= bits
+}
+
+/// Find a proof of work for `token` and `email` the way a signup form would,
+/// by trying counters in turn.
+pub fn solve(token: &str, email: &str, bits: u32) -> String {
+    (0u64..)
+        .map(|counter| counter.to_string())
+        .find(|solution| solves(token, email, solution, bits))
+        .expect("A solution exists for any reasonable difficulty")
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err_eq, assert_ok};
+    use time::{Duration, OffsetDateTime};
+
+    use super::{leading_zero_bits, solve, solves, ChallengeFailure, SignupChallenge};
+    use crate::configuration::SignupChallengeSettings;
+
+    const EMAIL: &str = "ursula@example.com";
+
+    fn challenge(
+        min_fill_seconds: Option<u64>,
+        proof_of_work_bits: Option<u32>,
+    ) -> SignupChallenge {
+        SignupChallenge::new(SignupChallengeSettings {
+            secret: "secret".into(),
+            min_fill_seconds,
+            proof_of_work_bits,
+            max_age_seconds: 3600,
+        })
+    }
+
+    #[test]
+    fn a_challenge_answered_in_time_passes() {
+        let challenge = challenge(Some(3), None);
+        let now = OffsetDateTime::now_utc();
+        let issued = challenge.issue(now);
+
+        assert_ok!(challenge.verify(Some(&issued.token), None, EMAIL, now + Duration::seconds(5)));
+    }
+
+    #[test]
+    fn a_challenge_answered_too_fast_fails() {
+        let challenge = challenge(Some(3), None);
+        let now = OffsetDateTime::now_utc();
+        let issued = challenge.issue(now);
+
+        assert_err_eq!(
+            challenge.verify(Some(&issued.token), None, EMAIL, now + Duration::seconds(1)),
+            ChallengeFailure::TooFast
+        );
+    }
+
+    #[test]
+    fn an_old_challenge_fails() {
+        let challenge = challenge(Some(3), None);
+        let now = OffsetDateTime::now_utc();
+        let issued = challenge.issue(now);
+
+        assert_err_eq!(
+            challenge.verify(Some(&issued.token), None, EMAIL, now + Duration::hours(2)),
+            ChallengeFailure::Expired
+        );
+    }
+
+    #[test]
+    fn a_backdated_challenge_fails() {
+        let challenge = challenge(Some(3), None);
+        let now = OffsetDateTime::now_utc();
+        let issued = challenge.issue(now);
+        let (issued_at, rest) = issued.token.split_once('.').unwrap();
+        let backdated = format!("{}.{}", issued_at.parse::<i64>().unwrap() - 60, rest);
+
+        assert_err_eq!(
+            challenge.verify(Some(&backdated), None, EMAIL, now),
+            ChallengeFailure::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn a_missing_or_garbled_challenge_fails() {
+        let challenge = challenge(Some(3), None);
+        let now = OffsetDateTime::now_utc();
+
+        assert_err_eq!(
+            challenge.verify(None, None, EMAIL, now),
+            ChallengeFailure::Missing
+        );
+        assert_err_eq!(
+            challenge.verify(Some("garbage"), None, EMAIL, now),
+            ChallengeFailure::Malformed
+        );
+    }
+
+    #[test]
+    fn the_proof_of_work_is_checked() {
+        let challenge = challenge(None, Some(8));
+        let now = OffsetDateTime::now_utc();
+        let issued = challenge.issue(now);
+        let solution = solve(&issued.token, EMAIL, 8);
+
+        assert_ok!(challenge.verify(Some(&issued.token), Some(&solution), EMAIL, now));
+        assert_err_eq!(
+            challenge.verify(Some(&issued.token), None, EMAIL, now),
+            ChallengeFailure::NoProofOfWork
+        );
+    }
+
+    #[test]
+    fn the_proof_of_work_only_holds_for_its_address() {
+        let challenge = challenge(None, Some(8));
+        let now = OffsetDateTime::now_utc();
+        let issued = challenge.issue(now);
+        let solution = (0u64..)
+            .map(|counter| counter.to_string())
+            .find(|solution| {
+                solves(&issued.token, EMAIL, solution, 8)
+                    && !solves(&issued.token, "octavia@example.com", solution, 8)
+            })
+            .unwrap();
+
+        assert_err_eq!(
+            challenge.verify(
+                Some(&issued.token),
+                Some(&solution),
+                "octavia@example.com",
+                now
+            ),
+            ChallengeFailure::NoProofOfWork
+        );
+    }
+
+    #[test]
+    fn leading_zero_bits_are_counted_across_bytes() {
+        assert_eq!(leading_zero_bits(&[0, 0b0001_0000, 0xff]), 11);
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+    }
+}
@@ -1,11 +1,13 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
-    extract::Request,
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo, Request},
     http::HeaderName,
-    routing::{get, post},
+    middleware::{self, AddExtension},
+    routing::{delete, get, post, put},
     Router,
 };
+use secrecy::SecretString;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use tokio::net::TcpListener;
 use tower::ServiceBuilder;
@@ -16,32 +18,84 @@ use tower_http::{
 use tracing::{error, info_span};
 
 use crate::{
-    configuration::{DatabaseSettings, Settings},
+    authentication::bootstrap_owner,
+    authorization::{require_permission, Permission},
+    client::identify_client,
+    configuration::{
+        ApplicationSettings, ConfirmationSettings, DatabaseSettings, EmailNormalisationSettings,
+        Settings,
+    },
+    deliverability::{DeliverabilityCheck, DnsResolver, DomainResolver},
     email_client::EmailClient,
-    routes::{confirm, health_check, subscribe},
+    email_policy::EmailPolicy,
+    email_templates::EmailTemplates,
+    rate_limit::{rate_limit, RateLimiter},
+    routes::{
+        change_locale, change_user_role, confirm, confirm_password_reset,
+        confirm_two_factor_enrollment, confirmation_page, create_list, create_list_topic,
+        create_user, delete_email_domain_rule, download_data_export, download_import_errors,
+        erase_subscriber_data, export_subscriber_data, export_subscribers, get_security_policy,
+        health_check, import_subscribers, issue_signup_challenge, issues_page, list_audit_log,
+        list_email_domain_rules, list_list_topics, list_lists, list_subscriber_consents,
+        list_users, login, login_page, login_two_factor, logout, password_reset_form,
+        preferences_page, publish_issue, redirect_to_login, request_data_export,
+        request_password_reset, save_issue, search_subscribers, set_email_domain_rule,
+        start_two_factor_enrollment, stats_page, submit_login, submit_logout, submit_two_factor,
+        subscribe, subscribe_to_list, subscribers_page, two_factor_page, update_preferences,
+        update_security_policy,
+    },
+    signup_challenge::SignupChallenge,
 };
 
 #[derive(Debug)]
 pub struct Application {
     port: u16,
-    server: axum::serve::Serve<TcpListener, Router, Router>,
+    server: axum::serve::Serve<
+        TcpListener,
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub database: PgPool,
     pub email_client: Arc<EmailClient>,
-    pub base_url: String
+    pub email_templates: Arc<EmailTemplates>,
+    pub base_url: String,
+    pub suppression_salt: SecretString,
+    pub confirmation: ConfirmationSettings,
+    pub rate_limiter: RateLimiter,
+    pub signup_challenge: Arc<SignupChallenge>,
+    pub email_policy: Arc<EmailPolicy>,
+    pub deliverability: Arc<DeliverabilityCheck>,
+    pub email_normalisation: EmailNormalisationSettings,
 }
 
 impl Application {
     pub async fn build(settings: Settings) -> Self {
+        let resolver = DnsResolver::new(&settings.application.deliverability);
+        Self::build_with_resolver(settings, Arc::new(resolver)).await
+    }
+
+    /// Build the application with `resolver` looking up the domains of new
+    /// subscriber addresses, rather than DNS.
+    pub async fn build_with_resolver(settings: Settings, resolver: Arc<dyn DomainResolver>) -> Self {
         let connection_pool = get_connection_pool(&settings.database);
+        if let Some(admin) = &settings.admin {
+            bootstrap_owner(&connection_pool, admin)
+                .await
+                .expect("Failed to bootstrap the owner account");
+        }
+
         let sender_email = settings
             .email_client
             .sender()
             .expect("Invalid sender email address");
 
+        let email_templates = EmailTemplates::load(&settings.email_templates)
+            .expect("Invalid email templates");
+
         let timeout = settings.email_client.timeout();
         let email_client = EmailClient::new(
             settings.email_client.base_url,
@@ -59,7 +113,13 @@ impl Application {
             .await
             .expect("Unable to bind to address");
         let port = listener.local_addr().unwrap().port();
-        let server = axum::serve(listener, run(connection_pool, email_client, settings.application.base_url));
+        let server = axum::serve(listener, run(
+            connection_pool,
+            email_client,
+            email_templates,
+            resolver,
+            settings.application,
+        ).into_make_service_with_connect_info::<SocketAddr>());
 
         Self { port, server }
     }
@@ -81,7 +141,7 @@ pub fn get_connection_pool(settings: &DatabaseSettings) -> PgPool {
 
 const REQUEST_ID_HEADER: &str = "x-request-id";
 
-pub fn run(db_pool: PgPool, email_client: EmailClient, base_url: String) -> Router {
+pub fn run(db_pool: PgPool, email_client: EmailClient, email_templates: EmailTemplates, resolver: Arc<dyn DomainResolver>, settings: ApplicationSettings) -> Router {
     let x_request_id = HeaderName::from_static(REQUEST_ID_HEADER);
 
     let tracing_middleware = ServiceBuilder::new()
@@ -106,16 +166,178 @@ pub fn run(db_pool: PgPool, email_client: EmailClient, base_url: String) -> Rout
         )
         .layer(PropagateRequestIdLayer::new(x_request_id));
 
+    let trusted_proxies = Arc::new(settings.rate_limit.trusted_proxies.clone());
     let state = AppState {
+        rate_limiter: RateLimiter::new(settings.rate_limit, db_pool.clone()),
         database: db_pool,
         email_client: Arc::new(email_client),
-        base_url
+        email_templates: Arc::new(email_templates),
+        base_url: settings.base_url,
+        suppression_salt: settings.suppression_salt,
+        confirmation: settings.confirmation,
+        signup_challenge: Arc::new(SignupChallenge::new(settings.signup_challenge)),
+        email_policy: Arc::new(
+            EmailPolicy::load(&settings.email_policy).expect("Invalid disposable domain list"),
+        ),
+        deliverability: Arc::new(DeliverabilityCheck::new(settings.deliverability, resolver)),
+        email_normalisation: settings.email_normalisation,
     };
 
+    let dashboard_routes = Router::new()
+        .route("/stats", get(stats_page))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_permission(Permission::ReadStats),
+        ))
+        .merge(
+            Router::new()
+                .route("/subscribers", get(subscribers_page))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_permission(Permission::ReadSubscribers),
+                )),
+        )
+        .merge(
+            Router::new()
+                .route("/issues", get(issues_page).post(save_issue))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_permission(Permission::DraftIssues),
+                )),
+        )
+        .merge(
+            Router::new()
+                .route("/issues/{issue_id}/publish", post(publish_issue))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_permission(Permission::PublishIssues),
+                )),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            redirect_to_login,
+        ))
+        .merge(
+            Router::new()
+                .route("/login", get(login_page).post(submit_login))
+                .route("/login/two-factor", get(two_factor_page).post(submit_two_factor))
+                .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit("login"))),
+        )
+        .route("/logout", post(submit_logout));
+
+    let admin_routes = Router::new()
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/{user_id}/role", put(change_user_role))
+        .route(
+            "/security-policy",
+            get(get_security_policy).put(update_security_policy),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_permission(Permission::ManageUsers),
+        ))
+        .merge(
+            Router::new()
+                .route("/subscribers", get(search_subscribers))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_permission(Permission::ReadSubscribers),
+                )),
+        )
+        .merge(
+            Router::new()
+                .route("/subscribers/export", get(export_subscribers))
+                .route("/subscribers/imports", post(import_subscribers))
+                .route("/lists", get(list_lists).post(create_list))
+                .route(
+                    "/lists/{slug}/topics",
+                    get(list_list_topics).post(create_list_topic),
+                )
+                .route("/email-domains", get(list_email_domain_rules))
+                .route(
+                    "/email-domains/{domain}",
+                    put(set_email_domain_rule).delete(delete_email_domain_rule),
+                )
+                .route("/subscribers/{subscriber_id}", delete(erase_subscriber_data))
+                .route(
+                    "/subscribers/{subscriber_id}/consents",
+                    get(list_subscriber_consents),
+                )
+                .route(
+                    "/subscribers/{subscriber_id}/data-export",
+                    get(export_subscriber_data),
+                )
+                .route(
+                    "/subscribers/imports/{import_id}/errors",
+                    get(download_import_errors),
+                )
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_permission(Permission::ManageSubscribers),
+                )),
+        )
+        .merge(
+            Router::new()
+                .route("/audit-log", get(list_audit_log))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_permission(Permission::ReadAuditLog),
+                )),
+        )
+        .route("/two-factor/enrollment", post(start_two_factor_enrollment))
+        .route(
+            "/two-factor/enrollment/confirm",
+            post(confirm_two_factor_enrollment),
+        )
+        .route("/logout", post(logout))
+        .merge(
+            Router::new()
+                .route("/login", post(login))
+                .route("/login/two-factor", post(login_two_factor))
+                .route("/password-reset", post(request_password_reset))
+                .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit("login"))),
+        )
+        .route(
+            "/password-reset/confirm",
+            get(password_reset_form).post(confirm_password_reset),
+        )
+        .nest("/dashboard", dashboard_routes);
+
+    let subscription_routes = Router::new()
+        .route("/", post(subscribe))
+        .route("/challenge", get(issue_signup_challenge))
+        .route("/confirm", get(confirmation_page).post(confirm))
+        .route(
+            "/data-export",
+            get(download_data_export).post(request_data_export),
+        )
+        .route("/locale", post(change_locale))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit("subscriptions"),
+        ));
+
+    let preference_routes = Router::new()
+        .route("/", get(preferences_page).post(update_preferences))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit("preferences"),
+        ));
+
+    let list_routes = Router::new()
+        .route("/{slug}/subscriptions", post(subscribe_to_list))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit("subscriptions"),
+        ));
+
     Router::new()
         .route("/health_check", get(health_check))
-        .route("/subscriptions", post(subscribe))
-        .route("/subscriptions/confirm", get(confirm))
+        .nest("/subscriptions", subscription_routes)
+        .nest("/lists", list_routes)
+        .nest("/preferences", preference_routes)
+        .nest("/admin", admin_routes)
+        .layer(middleware::from_fn_with_state(trusted_proxies, identify_client))
         .with_state(state)
         .layer(tracing_middleware)
 }
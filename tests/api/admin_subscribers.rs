@@ -0,0 +1,207 @@
+use reqwest::StatusCode;
+use time::{Duration, OffsetDateTime};
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, spawn_app_with_subscribers, TestApp, TestUser};
+
+async fn search(app: &TestApp, query: &str) -> serde_json::Value {
+    app.get_admin_subscribers(query)
+        .await
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+fn emails(page: &serde_json::Value) -> Vec<&str> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["email"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_newest_first_by_default() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    let page = search(&app, "").await;
+
+    // Assert
+    assert_eq!(
+        emails(&page),
+        [
+            "iain_banks@example.net",
+            "n.k@example.org",
+            "ted@example.org",
+            "octavia@example.com",
+            "ursula@example.com",
+        ]
+    );
+    assert!(page["next_cursor"].is_null());
+    assert!(page["total"].is_null());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+    let two_and_a_half_days_ago = (OffsetDateTime::now_utc() - Duration::hours(60))
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap();
+    let test_cases = [
+        (
+            "status=pending_confirmation",
+            vec!["iain_banks@example.net", "ted@example.org"],
+        ),
+        (
+            "email=EXAMPLE.ORG",
+            vec!["n.k@example.org", "ted@example.org"],
+        ),
+        ("email=_", vec!["iain_banks@example.net"]),
+        ("name=butler", vec!["octavia@example.com"]),
+        (
+            "status=confirmed&email=example.com",
+            vec!["octavia@example.com", "ursula@example.com"],
+        ),
+        (
+            &format!("subscribed_after={}", urlencode(&two_and_a_half_days_ago)),
+            vec!["iain_banks@example.net", "n.k@example.org"],
+        ),
+        (
+            &format!("subscribed_before={}", urlencode(&two_and_a_half_days_ago)),
+            vec![
+                "ted@example.org",
+                "octavia@example.com",
+                "ursula@example.com",
+            ],
+        ),
+    ];
+
+    for (query, expected) in test_cases {
+        // Act
+        let page = search(&app, query).await;
+
+        // Assert
+        assert_eq!(
+            emails(&page),
+            expected,
+            "Unexpected result for `{}`.",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn pages_can_be_walked_through_with_the_cursor() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    let newest_first = [
+        "iain_banks@example.net",
+        "n.k@example.org",
+        "ted@example.org",
+        "octavia@example.com",
+        "ursula@example.com",
+    ];
+    let mut oldest_first = newest_first;
+    oldest_first.reverse();
+
+    for (sort, expected) in [("asc", oldest_first), ("desc", newest_first)] {
+        // Act
+        let mut seen = Vec::new();
+        let mut query = format!("limit=2&sort={}", sort);
+        loop {
+            let page = search(&app, &query).await;
+            seen.extend(emails(&page).into_iter().map(String::from));
+            match page["next_cursor"].as_str() {
+                Some(cursor) => query = format!("limit=2&sort={}&cursor={}", sort, cursor),
+                None => break,
+            }
+        }
+
+        // Assert
+        assert_eq!(seen, expected);
+    }
+}
+
+#[tokio::test]
+async fn the_total_count_ignores_pagination() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    let page = search(&app, "status=confirmed&limit=1&include_total=true").await;
+
+    // Assert
+    assert_eq!(emails(&page).len(), 1);
+    assert_eq!(page["total"], 3);
+}
+
+#[tokio::test]
+async fn subscription_tokens_are_never_returned() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    let body = app.get_admin_subscribers("").await.text().await.unwrap();
+
+    // Assert
+    assert!(!body.contains("secret-token"));
+    assert!(!body.contains("token"));
+}
+
+#[tokio::test]
+async fn invalid_parameters_are_rejected() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    for query in ["cursor=garbage", "limit=0", "limit=201", "sort=sideways"] {
+        // Act
+        let response = app.get_admin_subscribers(query).await;
+
+        // Assert
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "`{}` was not rejected.",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn searching_subscribers_requires_a_session() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_subscribers("").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn viewers_and_editors_cannot_search_subscribers() {
+    for role in [Role::Viewer, Role::Editor] {
+        // Arrange
+        let app = spawn_app().await;
+        let user = TestUser::generate(role).store(&app.db_pool).await;
+        app.login(&user).await;
+
+        // Act
+        let response = app.get_admin_subscribers("").await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+fn urlencode(value: &str) -> String {
+    value.replace('+', "%2B").replace(':', "%3A")
+}
@@ -0,0 +1,211 @@
+use reqwest::StatusCode;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestUser};
+
+#[tokio::test]
+async fn anonymous_requests_to_admin_routes_are_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_users().await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn editors_and_viewers_cannot_manage_users() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for role in [Role::Editor, Role::Viewer] {
+        let user = TestUser::generate(role).store(&app.db_pool).await;
+        app.login(&user).await;
+
+        // Act
+        let list_response = app.get_admin_users().await;
+        let role_response = app.put_user_role(user.user_id, "owner").await;
+
+        // Assert
+        assert_eq!(list_response.status(), StatusCode::FORBIDDEN, "{:?}", role);
+        assert_eq!(role_response.status(), StatusCode::FORBIDDEN, "{:?}", role);
+    }
+}
+
+#[tokio::test]
+async fn owners_can_list_users() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    let editor = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.get_admin_users().await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let users: serde_json::Value = response.json().await.unwrap();
+    let users = users.as_array().unwrap();
+    assert_eq!(users.len(), 2);
+    let listed_editor = users
+        .iter()
+        .find(|u| u["username"] == editor.username.as_str())
+        .unwrap();
+    assert_eq!(listed_editor["role"], "editor");
+    assert!(listed_editor.get("password_hash").is_none());
+}
+
+#[tokio::test]
+async fn owners_can_create_users_that_can_then_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app
+        .post_admin_users(&serde_json::json!({
+            "username": "new-editor",
+            "password": "a-long-enough-password",
+            "role": "editor",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let login_response = app.post_login("new-editor", "a-long-enough-password").await;
+    assert_eq!(login_response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn creating_a_user_with_a_taken_username_returns_a_409() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app
+        .post_admin_users(&serde_json::json!({
+            "username": owner.username,
+            "password": "a-long-enough-password",
+            "role": "viewer",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn creating_a_user_with_a_taken_email_in_another_case_returns_a_409() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app
+        .post_admin_users(&serde_json::json!({
+            "username": "new-editor",
+            "email": owner.email.to_uppercase(),
+            "password": "a-long-enough-password",
+            "role": "editor",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn creating_a_user_with_an_invalid_email_returns_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app
+        .post_admin_users(&serde_json::json!({
+            "username": "new-editor",
+            "email": "not-an-email",
+            "password": "a-long-enough-password",
+            "role": "editor",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn owners_can_change_the_role_of_another_user() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    let viewer = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.put_user_role(viewer.user_id, "editor").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT role FROM users WHERE user_id = $1", viewer.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.role, "editor");
+}
+
+#[tokio::test]
+async fn the_last_owner_cannot_demote_themselves() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.put_user_role(owner.user_id, "editor").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let saved = sqlx::query!("SELECT role FROM users WHERE user_id = $1", owner.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.role, "owner");
+}
+
+#[tokio::test]
+async fn an_owner_can_step_down_when_another_owner_remains() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.put_user_role(owner.user_id, "viewer").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn changing_the_role_of_an_unknown_user_returns_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.put_user_role(uuid::Uuid::new_v4(), "editor").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,154 @@
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestUser};
+
+#[tokio::test]
+async fn role_changes_are_recorded_with_the_request_id() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    let viewer = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.put_user_role(viewer.user_id, "editor").await;
+
+    // Assert
+    let request_id = response.headers()["x-request-id"].to_str().unwrap();
+    let entry = sqlx::query!(
+        "SELECT actor_type, actor_id, target_id, request_id, before, after
+        FROM audit_log WHERE action = 'user.role_change'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(entry.actor_type, "user");
+    assert_eq!(entry.actor_id, Some(owner.user_id));
+    assert_eq!(entry.target_id, viewer.user_id.to_string());
+    assert_eq!(entry.request_id.as_deref(), Some(request_id));
+    assert_eq!(entry.before.unwrap()["role"], "viewer");
+    assert_eq!(entry.after.unwrap()["role"], "editor");
+}
+
+#[tokio::test]
+async fn subscription_state_transitions_are_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request).await;
+
+    // Act
+    app.confirm_subscription(&confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let entries = sqlx::query!(
+        "SELECT actor_type, action, before, after FROM audit_log ORDER BY id"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].action, "subscription.create");
+    assert_eq!(entries[1].action, "subscription.confirm");
+    assert_eq!(entries[1].actor_type, "subscriber");
+    assert_eq!(
+        entries[1].before.as_ref().unwrap()["status"],
+        "pending_confirmation"
+    );
+    assert_eq!(entries[1].after.as_ref().unwrap()["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn audit_entries_cannot_be_changed_or_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    let viewer = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    app.login(&owner).await;
+    app.put_user_role(viewer.user_id, "editor").await;
+
+    // Act
+    let update = sqlx::query!("UPDATE audit_log SET action = 'nothing.happened'")
+        .execute(&app.db_pool)
+        .await;
+    let delete = sqlx::query!("DELETE FROM audit_log")
+        .execute(&app.db_pool)
+        .await;
+
+    // Assert
+    assert!(update.is_err());
+    assert!(delete.is_err());
+}
+
+#[tokio::test]
+async fn the_audit_log_is_paginated_newest_first() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    let viewer = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    app.login(&owner).await;
+    for role in ["editor", "viewer", "editor"] {
+        app.put_user_role(viewer.user_id, role)
+            .await
+            .error_for_status()
+            .unwrap();
+    }
+
+    // Act - Part 1 - First page
+    let first_page: serde_json::Value = app
+        .get_audit_log("limit=2&action=user.role_change")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - Part 1
+    let entries = first_page["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["after"]["role"], "editor");
+    assert_eq!(entries[1]["after"]["role"], "viewer");
+    let cursor = first_page["next_cursor"].as_i64().unwrap();
+
+    // Act - Part 2 - Last page
+    let second_page: serde_json::Value = app
+        .get_audit_log(&format!("limit=2&action=user.role_change&before={}", cursor))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - Part 2
+    let entries = second_page["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["before"]["role"], "viewer");
+    assert!(second_page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn only_owners_can_read_the_audit_log() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&editor).await;
+
+    // Act
+    let response = app.get_audit_log("").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,138 @@
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::configuration::DeliverabilityMode;
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn undeliverable_domains_are_rejected_with_a_suggestion_in_hard_mode() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.deliverability.mode = DeliverabilityMode::Hard).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmial.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["field"], "email");
+    assert_eq!(body["errors"][0]["code"], "undeliverable");
+    assert_eq!(body["errors"][0]["suggestion"], "ursula@gmail.com");
+    assert_eq!(app.subscriber_count().await, 0);
+}
+
+#[tokio::test]
+async fn undeliverable_domains_without_a_likely_typo_get_no_suggestion() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.deliverability.mode = DeliverabilityMode::Hard).await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40no-mail-here.test".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["code"], "undeliverable");
+    assert!(body["errors"][0]["suggestion"].is_null());
+}
+
+#[tokio::test]
+async fn deliverable_domains_are_accepted_in_hard_mode() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.deliverability.mode = DeliverabilityMode::Hard).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn non_ascii_spellings_of_a_domain_are_looked_up_in_their_ascii_form() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.deliverability.mode = DeliverabilityMode::Hard).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let body =
+        serde_urlencoded::to_string([("name", "le guin"), ("email", "ursula@ｅｘａｍｐｌｅ.com")])
+            .unwrap();
+    let response = app.post_subscriptions(body).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn undeliverable_domains_are_let_through_in_soft_mode() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.deliverability.mode = DeliverabilityMode::Soft).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmial.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn domains_are_not_checked_by_default() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmial.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,201 @@
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestUser};
+
+#[tokio::test]
+async fn addresses_at_disposable_domains_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40eu.mailinator.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["field"], "email");
+    assert_eq!(body["errors"][0]["code"], "not_accepted");
+    assert_eq!(app.subscriber_count().await, 0);
+}
+
+#[tokio::test]
+async fn role_addresses_are_accepted_but_flagged() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=postmaster%40example.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    app.login(&owner).await;
+    let page: serde_json::Value = app
+        .get_admin_subscribers("flagged=true")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let subscribers = page["subscribers"].as_array().unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "postmaster@example.com");
+    assert_eq!(subscribers[0]["flagged_reason"], "role_address");
+}
+
+#[tokio::test]
+async fn admins_can_block_a_domain_and_its_subdomains() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+
+    // Act
+    let response = app.put_email_domain_rule("Spam.Example", "block").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40mail.spam.example".into())
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let entry = sqlx::query!(
+        "SELECT target_id, after FROM audit_log WHERE action = 'email_domain.rule_change'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(entry.target_id, "spam.example");
+    assert_eq!(entry.after.unwrap()["rule"], "block");
+}
+
+#[tokio::test]
+async fn fullwidth_spellings_of_disposable_domains_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let body = serde_urlencoded::to_string([
+        ("name", "le guin"),
+        ("email", "ursula@ｍａｉｌｉｎａｔｏｒ.com"),
+    ])
+    .unwrap();
+    let response = app.post_subscriptions(body).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["code"], "not_accepted");
+    assert_eq!(app.subscriber_count().await, 0);
+}
+
+#[tokio::test]
+async fn blocking_an_internationalised_domain_covers_its_unicode_spelling() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    app.put_email_domain_rule("xn--bcher-kva.example", "block")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let body =
+        serde_urlencoded::to_string([("name", "le guin"), ("email", "ursula@Bücher.example")])
+            .unwrap();
+    let response = app.post_subscriptions(body).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(app.subscriber_count().await, 0);
+}
+
+#[tokio::test]
+async fn allowed_domains_are_let_in_even_if_disposable() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.put_email_domain_rule("yopmail.com", "allow")
+        .await
+        .error_for_status()
+        .unwrap();
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40yopmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn removing_a_rule_restores_the_default_checks() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    app.put_email_domain_rule("yopmail.com", "allow")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let first = app.delete_email_domain_rule("yopmail.com").await;
+    let second = app.delete_email_domain_rule("yopmail.com").await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::NOT_FOUND);
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40yopmail.com".into())
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn editors_cannot_manage_domain_rules() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&editor).await;
+
+    // Act
+    let response = app.put_email_domain_rule("spam.example", "block").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -1,9 +1,19 @@
+use std::sync::Arc;
+
 use once_cell::sync::Lazy;
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
+    authentication::compute_password_hash,
+    authorization::Role,
+    configuration::{get_configuration, DatabaseSettings, Settings},
+    deliverability::StaticResolver,
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -24,6 +34,45 @@ pub struct TestApp {
     pub port: u16,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub api_client: reqwest::Client,
+}
+
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    pub role: Role,
+}
+
+impl TestUser {
+    pub fn generate(role: Role) -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+            password: Uuid::new_v4().to_string(),
+            role,
+        }
+    }
+
+    pub async fn store(self, pool: &PgPool) -> Self {
+        let password_hash = compute_password_hash(SecretString::from(self.password.clone()))
+            .expect("Failed to hash password.");
+        sqlx::query!(
+            "INSERT INTO users (user_id, username, email, password_hash, role)
+            VALUES ($1, $2, $3, $4, $5)",
+            self.user_id,
+            self.username,
+            self.email,
+            password_hash.expose_secret(),
+            self.role.as_str(),
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to store test user.");
+        self
+    }
 }
 
 pub struct ConfirmationLinks {
@@ -42,6 +91,433 @@ impl TestApp {
             .expect("Failed to send request.")
     }
 
+    pub async fn post_list_subscriptions(&self, slug: &str, body: String) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/lists/{}/subscriptions", self.address, slug))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    /// Subscribe `email` to the default list and confirm, returning the
+    /// subscription token, which also opens the preference centre.
+    pub async fn confirmed_subscriber(&self, name: &str, email: &str) -> String {
+        let body = serde_urlencoded::to_string([("name", name), ("email", email)]).unwrap();
+        self.post_subscriptions(body).await.error_for_status().unwrap();
+        let token = self.subscription_token(email).await;
+        let confirmation_link = reqwest::Url::parse_with_params(
+            &format!("{}/subscriptions/confirm", self.address),
+            [("subscription_token", &token)],
+        )
+        .unwrap();
+        self.confirm_subscription(&confirmation_link).await.error_for_status().unwrap();
+        token
+    }
+
+    /// The subscription token sent to `email`.
+    pub async fn subscription_token(&self, email: &str) -> String {
+        sqlx::query_scalar!(
+            "SELECT t.subscription_token FROM subscription_tokens t
+            JOIN subscriptions s ON s.id = t.subscriber_id
+            WHERE s.email = $1",
+            email,
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .unwrap()
+    }
+
+    /// The id of the subscriber saved under `email`.
+    pub async fn subscriber_id(&self, email: &str) -> Uuid {
+        sqlx::query_scalar!("SELECT id FROM subscriptions WHERE email = $1", email)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+    }
+
+    /// The number of subscribers saved, whatever their status.
+    pub async fn subscriber_count(&self) -> i64 {
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+    }
+
+    /// Submit the page a confirmation link lands on.
+    pub async fn confirm_subscription(&self, confirmation_link: &reqwest::Url) -> reqwest::Response {
+        let token = confirmation_link
+            .query_pairs()
+            .find(|(k, _)| k == "subscription_token")
+            .map(|(_, v)| v.into_owned())
+            .unwrap();
+        self.api_client
+            .post(format!("{}/subscriptions/confirm", self.address))
+            .form(&[("subscription_token", token)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_login(&self, username: &str, password: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/login", self.address))
+            .form(&[("username", username), ("password", password)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    /// Log in as `user`, keeping the session cookie in `api_client`.
+    pub async fn login(&self, user: &TestUser) {
+        self.post_login(&user.username, &user.password)
+            .await
+            .error_for_status()
+            .expect("Failed to log in.");
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/logout", self.address))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_login_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/login/two-factor", self.address))
+            .form(&[("code", code)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_two_factor_enrollment(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/two-factor/enrollment", self.address))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_two_factor_enrollment_confirmation(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/two-factor/enrollment/confirm", self.address))
+            .form(&[("code", code)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    /// Enroll the logged in user in TOTP, returning the secret and the
+    /// recovery codes.
+    pub async fn enroll_two_factor(&self) -> (String, Vec<String>) {
+        let enrollment: serde_json::Value = self
+            .post_two_factor_enrollment()
+            .await
+            .error_for_status()
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let secret = enrollment["secret"].as_str().unwrap().to_string();
+
+        let confirmation: serde_json::Value = self
+            .post_two_factor_enrollment_confirmation(&totp_code(&secret, 0))
+            .await
+            .error_for_status()
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let recovery_codes = confirmation["recovery_codes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c.as_str().unwrap().to_string())
+            .collect();
+
+        (secret, recovery_codes)
+    }
+
+    pub async fn put_security_policy(&self, require_two_factor: bool) -> reqwest::Response {
+        self.api_client
+            .put(format!("{}/admin/security-policy", self.address))
+            .json(&serde_json::json!({ "require_two_factor": require_two_factor }))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_audit_log(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/audit-log?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_admin_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_subscriber_import(&self, csv: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/subscribers/imports", self.address))
+            .header("Content-Type", "text/csv")
+            .body(csv)
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_subscriber_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/export?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_subscriber_data_export(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}/data-export",
+                self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_subscriber_consents(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}/consents",
+                self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn delete_subscriber(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .delete(format!(
+                "{}/admin/subscribers/{}",
+                self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_data_export_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/data-export", self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_signup_challenge(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/subscriptions/challenge", self.address))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_locale_change(&self, subscription_token: &str, locale: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/locale", self.address))
+            .form(&[("subscription_token", subscription_token), ("locale", locale)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_preferences(&self, subscription_token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/preferences", self.address))
+            .query(&[("subscription_token", subscription_token)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_preferences(&self, form: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/preferences", self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_list_topics(&self, slug: &str, topic: &str, name: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lists/{}/topics", self.address, slug))
+            .json(&serde_json::json!({ "slug": topic, "name": name }))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_admin_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", self.address))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_admin_users(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn put_user_role(&self, user_id: Uuid, role: &str) -> reqwest::Response {
+        self.api_client
+            .put(format!("{}/admin/users/{}/role", self.address, user_id))
+            .json(&serde_json::json!({ "role": role }))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn put_email_domain_rule(&self, domain: &str, rule: &str) -> reqwest::Response {
+        self.api_client
+            .put(format!("{}/admin/email-domains/{}", self.address, domain))
+            .json(&serde_json::json!({ "rule": rule }))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn delete_email_domain_rule(&self, domain: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/admin/email-domains/{}", self.address, domain))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_admin_lists(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/lists", self.address))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_admin_lists(&self, slug: &str, name: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lists", self.address))
+            .json(&serde_json::json!({ "slug": slug, "name": name }))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_password_reset(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/password-reset", self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_password_reset_confirmation(
+        &self,
+        token: &str,
+        password: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/password-reset/confirm", self.address))
+            .form(&[("token", token), ("password", password)])
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn get_dashboard(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/dashboard{}", self.address, path))
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_dashboard(&self, path: &str, form: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/dashboard{}", self.address, path))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    /// The CSRF token the dashboard login page hands to `api_client`.
+    pub async fn dashboard_csrf_token(&self) -> String {
+        let page = self.get_dashboard("/login").await.text().await.unwrap();
+        let (_, rest) = page
+            .split_once(r#"name="csrf_token" value=""#)
+            .expect("The page has no CSRF token.");
+        rest[..rest.find('"').unwrap()].to_string()
+    }
+
+    /// Log in as `user` through the dashboard, returning the CSRF token to
+    /// submit with further forms.
+    pub async fn dashboard_login(&self, user: &TestUser) -> String {
+        let csrf_token = self.dashboard_csrf_token().await;
+        let response = self
+            .post_dashboard(
+                "/login",
+                &[
+                    ("username", &user.username),
+                    ("password", &user.password),
+                    ("csrf_token", &csrf_token),
+                ],
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), 303);
+        csrf_token
+    }
+
+    /// Accept every email sent through the email API.
+    pub async fn mock_email_server(&self) {
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+    }
+
+    /// Wait for emails sent in the background to reach the mock server.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<wiremock::Request> {
+        for _ in 0..50 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("Expected {} emails to be sent.", count);
+    }
+
     pub async fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         // Parse the body as JSON, starting from raw bytes
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
@@ -54,7 +530,7 @@ impl TestApp {
 
             assert_eq!(links.len(), 1);
             let raw_link = links[0].as_str();
-            let mut confirmation_link = reqwest::Url::parse(&raw_link).unwrap();
+            let mut confirmation_link = reqwest::Url::parse(raw_link).unwrap();
             // Let's make sure we don't call random APIs on the web
             assert_eq!(confirmation_link.host_str().unwrap(), "127.0.0.1");
             confirmation_link.set_port(Some(self.port)).unwrap();
@@ -67,8 +543,29 @@ impl TestApp {
     }
 }
 
-#[allow(clippy::let_underscore_future)]
+/// The TOTP code for `secret`, `step_offset` time steps away from now.
+pub fn totp_code(secret: &str, step_offset: i64) -> String {
+    let totp = totp_rs::TOTP::new(
+        totp_rs::Algorithm::SHA1,
+        6,
+        1,
+        30,
+        totp_rs::Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap();
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    totp.generate((now + step_offset * 30) as u64)
+}
+
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawn the application with `configure` applied to the test configuration.
+#[allow(clippy::let_underscore_future)]
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -80,26 +577,85 @@ pub async fn spawn_app() -> TestApp {
         c.application.port = 0;
         // Use the mock server as email API
         c.email_client.base_url = email_server.uri();
+        // Tests create the users they need
+        c.admin = None;
+        configure(&mut c);
         c
     };
 
     // Create and migrate the database
     configure_database(&configuration.database).await;
 
-    // Launch the application as a background task
-    let application = Application::build(configuration.clone()).await;
+    // Launch the application as a background task, without DNS lookups
+    let resolver = StaticResolver::new(["example.com", "example.net", "example.org", "gmail.com"]);
+    let application = Application::build_with_resolver(configuration.clone(), Arc::new(resolver)).await;
     let port = application.port();
     let address = format!("http://127.0.0.1:{}", port);
     let _ = tokio::spawn(application.run_until_stopped());
 
+    let api_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+
     TestApp {
         address,
         port,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
+        api_client,
     }
 }
 
+/// Spawn the application with five subscribers, from five days ago to
+/// yesterday, and an owner logged in.
+pub async fn spawn_app_with_subscribers() -> TestApp {
+    let app = spawn_app().await;
+    let subscribers = [
+        ("ursula@example.com", "Le Guin, Ursula", "confirmed", 5),
+        ("octavia@example.com", "Octavia Butler", "confirmed", 4),
+        ("ted@example.org", "Ted Chiang", "pending_confirmation", 3),
+        ("n.k@example.org", "N. K. Jemisin", "confirmed", 2),
+        ("iain_banks@example.net", "Iain Banks", "pending_confirmation", 1),
+    ];
+    for (email, name, status, days_ago) in subscribers {
+        store_subscriber(&app.db_pool, email, name, status, days_ago).await;
+    }
+
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    app
+}
+
+/// Store a subscriber who subscribed `days_ago` days ago, with a token.
+async fn store_subscriber(pool: &PgPool, email: &str, name: &str, status: &str, days_ago: i64) {
+    let id = Uuid::new_v4();
+    let subscribed_at = OffsetDateTime::now_utc() - Duration::days(days_ago);
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, normalised_email, name, subscribed_at, confirmed_at, status)
+        VALUES ($1, $2, $2, $3, $4, $5, $6)",
+        id,
+        email,
+        name,
+        subscribed_at,
+        (status == "confirmed").then_some(subscribed_at),
+        status,
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        SELECT $1, $2, list_id FROM lists WHERE is_default",
+        format!("secret-token-{}", id.simple()),
+        id,
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
     // Create database
     let mut connection = PgConnection::connect_with(&config.without_db())
@@ -0,0 +1,213 @@
+use reqwest::StatusCode;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestApp, TestUser};
+
+const BODY: &str = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+async fn create_list(app: &TestApp, slug: &str) {
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    let response = app.post_admin_lists(slug, "Weekly digest").await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+/// The status of the only subscriber on the list with `slug`, if they joined it.
+async fn membership_status(app: &TestApp, slug: &str) -> Option<String> {
+    sqlx::query_scalar!(
+        "SELECT m.status FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE l.slug = $1",
+        slug,
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn admins_can_create_lists() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    create_list(&app, "weekly").await;
+
+    // Assert
+    let lists: serde_json::Value = app.get_admin_lists().await.json().await.unwrap();
+    let slugs: Vec<_> = lists
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|list| list["slug"].as_str().unwrap())
+        .collect();
+    assert_eq!(slugs, ["newsletter", "weekly"]);
+}
+
+#[tokio::test]
+async fn lists_need_a_new_and_url_friendly_slug() {
+    // Arrange
+    let app = spawn_app().await;
+    create_list(&app, "weekly").await;
+
+    // Act
+    let invalid = app.post_admin_lists("Weekly Digest", "Weekly digest").await;
+    let duplicate = app.post_admin_lists("weekly", "Another weekly").await;
+
+    // Assert
+    assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_returns_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_list_subscriptions("nope", BODY.into()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn subscriptions_without_a_list_join_the_default_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+
+    // Act
+    app.post_subscriptions(BODY.into()).await;
+
+    // Assert
+    assert_eq!(
+        membership_status(&app, "newsletter").await.as_deref(),
+        Some("pending_confirmation")
+    );
+}
+
+#[tokio::test]
+async fn each_list_is_confirmed_on_its_own() {
+    // Arrange
+    let app = spawn_app().await;
+    create_list(&app, "weekly").await;
+    app.mock_email_server().await;
+    app.post_subscriptions(BODY.into()).await;
+    app.post_list_subscriptions("weekly", BODY.into()).await;
+    let emails = app.wait_for_emails(2).await;
+    let weekly_links = app.get_confirmation_links(&emails[1]).await;
+
+    // Act
+    let response = app.confirm_subscription(&weekly_links.html).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        membership_status(&app, "weekly").await.as_deref(),
+        Some("confirmed")
+    );
+    assert_eq!(
+        membership_status(&app, "newsletter").await.as_deref(),
+        Some("pending_confirmation")
+    );
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_are_asked_to_confirm_another_list() {
+    // Arrange
+    let app = spawn_app().await;
+    create_list(&app, "weekly").await;
+    app.mock_email_server().await;
+    app.post_subscriptions(BODY.into()).await;
+    let emails = app.wait_for_emails(1).await;
+    let links = app.get_confirmation_links(&emails[0]).await;
+    app.confirm_subscription(&links.html).await;
+
+    // Act
+    let response = app.post_list_subscriptions("weekly", BODY.into()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    app.wait_for_emails(2).await;
+    assert_eq!(
+        membership_status(&app, "weekly").await.as_deref(),
+        Some("pending_confirmation")
+    );
+    assert_eq!(
+        membership_status(&app, "newsletter").await.as_deref(),
+        Some("confirmed")
+    );
+}
+
+#[tokio::test]
+async fn issues_are_only_delivered_to_the_members_of_their_list() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    app.post_admin_lists("weekly", "Weekly digest").await;
+    app.mock_email_server().await;
+    app.post_subscriptions("name=ursula&email=ursula%40example.com".into())
+        .await;
+    app.post_list_subscriptions("weekly", "name=octavia&email=octavia%40example.com".into())
+        .await;
+    for email in app.wait_for_emails(2).await {
+        let links = app.get_confirmation_links(&email).await;
+        app.confirm_subscription(&links.html).await;
+    }
+    let csrf_token = app.dashboard_login(&owner).await;
+    app.post_dashboard(
+        "/issues",
+        &[
+            ("title", "Weekly title"),
+            ("text_content", "Weekly body as plain text"),
+            ("html_content", "<p>Weekly body as HTML</p>"),
+            ("list", "weekly"),
+            ("csrf_token", &csrf_token),
+        ],
+    )
+    .await;
+    let issue_id = sqlx::query_scalar!("SELECT issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    app.post_dashboard(
+        &format!("/issues/{}/publish", issue_id),
+        &[("csrf_token", &csrf_token)],
+    )
+    .await;
+
+    // Assert
+    let emails = app.wait_for_emails(3).await;
+    let body: serde_json::Value = serde_json::from_slice(&emails[2].body).unwrap();
+    assert_eq!(body["subject"], "Weekly title");
+    assert_eq!(body["to"][0]["email"], "octavia@example.com");
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let deliveries = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries, 1);
+}
+
+#[tokio::test]
+async fn topics_belong_to_an_existing_list_and_have_a_new_slug() {
+    // Arrange
+    let app = spawn_app().await;
+    create_list(&app, "weekly").await;
+
+    // Act
+    let created = app.post_list_topics("weekly", "books", "Books").await;
+    let duplicate = app.post_list_topics("weekly", "books", "More books").await;
+    let same_slug_elsewhere = app.post_list_topics("newsletter", "books", "Books").await;
+    let unknown_list = app.post_list_topics("nope", "books", "Books").await;
+
+    // Assert
+    assert_eq!(created.status(), StatusCode::CREATED);
+    assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+    assert_eq!(same_slug_elsewhere.status(), StatusCode::CREATED);
+    assert_eq!(unknown_list.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,66 @@
+use reqwest::StatusCode;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestUser};
+
+#[tokio::test]
+async fn login_with_valid_credentials_sets_a_session_cookie() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+
+    // Act
+    let response = app.post_login(&user.username, &user.password).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = response
+        .cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("No session cookie was set.");
+    assert!(cookie.http_only());
+}
+
+#[tokio::test]
+async fn login_with_a_wrong_password_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+
+    // Act
+    let response = app.post_login(&user.username, "not-the-password").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_with_an_unknown_username_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_login("nobody", "not-the-password").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_session() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    assert_eq!(app.get_admin_users().await.status(), StatusCode::OK);
+
+    // Act
+    let response = app.post_logout().await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        app.get_admin_users().await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
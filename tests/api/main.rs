@@ -1,4 +1,23 @@
+mod admin_dashboard;
+mod admin_subscribers;
+mod admin_users;
+mod audit_log;
+mod deliverability;
+mod email_policy;
 mod health_check;
 mod helpers;
+mod lists;
+mod login;
+mod password_reset;
+mod preferences;
+mod rate_limiting;
+mod signup_challenge;
+mod subscriber_consents;
+mod subscriber_data_export;
+mod subscriber_erasure;
+mod subscriber_export;
+mod subscriber_import;
+mod subscriber_locale;
 mod subscriptions;
 mod subscriptions_confirm;
+mod two_factor;
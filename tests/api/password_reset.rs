@@ -0,0 +1,167 @@
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestApp, TestUser};
+
+async fn request_reset_token(app: &TestApp, user: &TestUser) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_password_reset(&user.email)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.wait_for_emails(1).await[0];
+    let links = app.get_confirmation_links(email_request).await;
+    links
+        .html
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn requesting_a_reset_emails_a_link_to_known_admins() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+
+    // Act
+    let token = request_reset_token(&app, &user).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], user.email.as_str());
+    let stored = sqlx::query!("SELECT token_hash FROM password_reset_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.token_hash, token);
+}
+
+#[tokio::test]
+async fn the_response_is_the_same_whether_or_not_the_account_exists() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let known = app.post_password_reset(&user.email).await;
+    let unknown = app.post_password_reset("nobody@example.com").await;
+
+    // Assert
+    assert_eq!(known.status(), unknown.status());
+    assert_eq!(known.text().await.unwrap(), unknown.text().await.unwrap());
+    app.wait_for_emails(1).await;
+}
+
+#[tokio::test]
+async fn a_reset_link_sets_a_new_password() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    let token = request_reset_token(&app, &user).await;
+
+    // Act
+    let response = app
+        .post_password_reset_confirmation(&token, "a-brand-new-password")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let old_login = app.post_login(&user.username, &user.password).await;
+    assert_eq!(old_login.status(), StatusCode::UNAUTHORIZED);
+    let new_login = app.post_login(&user.username, "a-brand-new-password").await;
+    assert_eq!(new_login.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    let token = request_reset_token(&app, &user).await;
+    app.post_password_reset_confirmation(&token, "a-brand-new-password")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_password_reset_confirmation(&token, "yet-another-password")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    let token = request_reset_token(&app, &user).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_password_reset_confirmation(&token, "a-brand-new-password")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn resetting_a_password_ends_existing_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    let token = request_reset_token(&app, &user).await;
+
+    // Act
+    app.post_password_reset_confirmation(&token, "a-brand-new-password")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        app.get_admin_users().await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn a_too_short_new_password_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    let token = request_reset_token(&app, &user).await;
+
+    // Act
+    let response = app.post_password_reset_confirmation(&token, "short").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let login = app.post_login(&user.username, &user.password).await;
+    assert_eq!(login.status(), StatusCode::OK);
+}
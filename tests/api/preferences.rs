@@ -0,0 +1,230 @@
+use reqwest::StatusCode;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestApp, TestUser};
+
+/// Log in as an owner and give the default list two topics.
+async fn create_topics(app: &TestApp) -> TestUser {
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    for (slug, name) in [("books", "Books"), ("films", "Films")] {
+        let response = app.post_list_topics("newsletter", slug, name).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+    owner
+}
+
+/// Draft and publish an issue of the default list about `topic`, if any.
+async fn publish_issue(app: &TestApp, owner: &TestUser, topic: Option<&str>) {
+    let csrf_token = app.dashboard_login(owner).await;
+    let mut form = vec![
+        ("title", "Issue title".to_string()),
+        ("text_content", "Issue body as plain text".to_string()),
+        ("html_content", "<p>Issue body as HTML</p>".to_string()),
+        ("csrf_token", csrf_token.clone()),
+    ];
+    if let Some(topic) = topic {
+        let topic_id = sqlx::query_scalar!("SELECT topic_id FROM topics WHERE slug = $1", topic)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        form.push(("topics", topic_id.to_string()));
+    }
+    let form: Vec<(&str, &str)> = form.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    app.post_dashboard("/issues", &form).await;
+    let issue_id = sqlx::query_scalar!("SELECT issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_dashboard(
+        &format!("/issues/{}/publish", issue_id),
+        &[("csrf_token", &csrf_token)],
+    )
+    .await;
+}
+
+/// The addresses an issue was delivered to, once delivery settled.
+async fn recipients(app: &TestApp, expected: usize) -> Vec<String> {
+    let mut recipients = Vec::new();
+    for _ in 0..50 {
+        recipients = sqlx::query_scalar!(
+            "SELECT s.email FROM issue_deliveries d
+            JOIN subscriptions s ON s.id = d.subscriber_id
+            ORDER BY s.email"
+        )
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+        if recipients.len() >= expected {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    recipients
+}
+
+#[tokio::test]
+async fn preferences_need_the_token_of_a_confirmed_subscription() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+    let pending_token = app.subscription_token("ursula@example.com").await;
+
+    // Act
+    let unknown = app.get_preferences("not-a-token").await;
+    let pending = app.get_preferences(&pending_token).await;
+
+    // Assert
+    assert_eq!(unknown.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(pending.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn the_preference_centre_shows_the_topics_of_the_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    create_topics(&app).await;
+    let token = app
+        .confirmed_subscriber("Ursula", "ursula@example.com")
+        .await;
+
+    // Act
+    let response = app.get_preferences(&token).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Topics you receive from Newsletter"));
+    assert!(page.contains("Pause delivery of all my lists"));
+    assert!(page.contains(r#"value="Ursula""#));
+    assert!(page.contains(r#"value="books" checked"#));
+    assert!(page.contains(r#"value="films" checked"#));
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name_topics_and_pause_delivery() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    create_topics(&app).await;
+    let token = app
+        .confirmed_subscriber("Ursula", "ursula@example.com")
+        .await;
+
+    // Act
+    let response = app
+        .post_preferences(&[
+            ("subscription_token", &token),
+            ("name", "Ursula K. Le Guin"),
+            ("topics", "books"),
+            ("paused", "true"),
+        ])
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Your preferences were saved."));
+    assert!(page.contains(r#"value="books" checked"#));
+    assert!(!page.contains(r#"value="films" checked"#));
+
+    let subscriber = sqlx::query!("SELECT name, delivery_paused_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.name, "Ursula K. Le Guin");
+    assert!(subscriber.delivery_paused_at.is_some());
+    let opted_out = sqlx::query_scalar!(
+        "SELECT t.slug FROM topic_opt_outs o JOIN topics t ON t.topic_id = o.topic_id"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(opted_out, ["films"]);
+}
+
+#[tokio::test]
+async fn invalid_names_are_refused_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    let token = app
+        .confirmed_subscriber("Ursula", "ursula@example.com")
+        .await;
+
+    // Act
+    let response = app
+        .post_preferences(&[("subscription_token", &token), ("name", "  ")])
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("The name is empty."));
+    let name = sqlx::query_scalar!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(name, "Ursula");
+}
+
+#[tokio::test]
+async fn issues_about_a_topic_skip_the_subscribers_who_turned_it_off() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    let owner = create_topics(&app).await;
+    app.confirmed_subscriber("Ursula", "ursula@example.com")
+        .await;
+    let token = app
+        .confirmed_subscriber("Octavia", "octavia@example.com")
+        .await;
+    app.post_preferences(&[
+        ("subscription_token", &token),
+        ("name", "Octavia"),
+        ("topics", "books"),
+    ])
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Act
+    publish_issue(&app, &owner, Some("films")).await;
+
+    // Assert
+    assert_eq!(recipients(&app, 1).await, ["ursula@example.com"]);
+}
+
+#[tokio::test]
+async fn paused_subscribers_receive_no_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.confirmed_subscriber("Ursula", "ursula@example.com")
+        .await;
+    let token = app
+        .confirmed_subscriber("Octavia", "octavia@example.com")
+        .await;
+    app.post_preferences(&[
+        ("subscription_token", &token),
+        ("name", "Octavia"),
+        ("paused", "true"),
+    ])
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Act
+    publish_issue(&app, &owner, None).await;
+
+    // Assert
+    assert_eq!(recipients(&app, 1).await, ["ursula@example.com"]);
+}
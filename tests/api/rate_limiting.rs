@@ -0,0 +1,217 @@
+use reqwest::StatusCode;
+use zero2prod::configuration::{RateLimit, RateLimitBackend};
+
+use crate::helpers::{spawn_app_with, TestApp};
+
+async fn subscribe_from(app: &TestApp, forwarded_for: &str, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions", app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .form(&[("name", "le guin"), ("email", email)])
+        .send()
+        .await
+        .expect("Failed to send request.")
+}
+
+fn limit(requests: u32) -> RateLimit {
+    RateLimit {
+        requests,
+        window_seconds: 3600,
+    }
+}
+
+#[tokio::test]
+async fn clients_over_their_limit_are_told_when_to_come_back() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.rate_limit.per_ip = limit(2)).await;
+    app.mock_email_server().await;
+    for i in 0..2 {
+        app.post_subscriptions(format!("name=le%20guin&email=ursula{}%40gmail.com", i))
+            .await
+            .error_for_status()
+            .unwrap();
+    }
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula2%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=3600).contains(&retry_after));
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn an_address_cannot_be_flooded_from_many_clients() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.rate_limit.per_email = limit(2);
+        c.application.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+    app.mock_email_server().await;
+
+    // Act
+    let mut statuses = Vec::new();
+    for i in 1..=3 {
+        let forwarded_for = format!("203.0.113.{}", i);
+        let response = subscribe_from(&app, &forwarded_for, "Ursula_Le_Guin@gmail.com").await;
+        statuses.push(response.status());
+    }
+
+    // Assert
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_used_behind_trusted_proxies() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.rate_limit.per_ip = limit(1);
+        c.application.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+    app.mock_email_server().await;
+
+    // Act
+    let first = subscribe_from(&app, "203.0.113.1", "ursula@gmail.com").await;
+    let second = subscribe_from(&app, "203.0.113.2", "le_guin@gmail.com").await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::OK);
+    let ip_addresses = sqlx::query_scalar!(
+        r#"SELECT ip_address AS "ip_address!" FROM consents ORDER BY recorded_at"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(ip_addresses, vec!["203.0.113.1", "203.0.113.2"]);
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_ignored_from_untrusted_peers() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.rate_limit.per_ip = limit(1)).await;
+    app.mock_email_server().await;
+
+    // Act
+    let first = subscribe_from(&app, "203.0.113.1", "ursula@gmail.com").await;
+    let second = subscribe_from(&app, "203.0.113.2", "le_guin@gmail.com").await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn limits_can_be_shared_through_postgres() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.rate_limit.backend = RateLimitBackend::Postgres;
+        c.application.rate_limit.per_email = limit(1);
+    })
+    .await;
+    app.mock_email_server().await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_data_export_request("ursula_le_guin@gmail.com")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let counter =
+        sqlx::query!("SELECT key, count FROM rate_limit_counters WHERE key LIKE 'email:%'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(counter.count, 2);
+    assert!(!counter.key.contains("ursula"));
+}
+
+#[tokio::test]
+async fn aliases_of_an_address_share_its_limit() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.rate_limit.per_email = limit(1);
+        c.application.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+        c.application.email_normalisation.fold_provider_aliases = true;
+    })
+    .await;
+    app.mock_email_server().await;
+    subscribe_from(&app, "203.0.113.1", "ursula.le.guin@gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = subscribe_from(&app, "203.0.113.2", "UrsulaLeGuin+news@googlemail.com").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn admin_logins_are_rate_limited() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.rate_limit.per_ip = limit(2)).await;
+
+    // Act
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let response = app.post_login("admin", "guessing-the-password").await;
+        statuses.push(response.status());
+    }
+    let dashboard = app.get_dashboard("/login").await;
+
+    // Assert
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::UNAUTHORIZED,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+    assert_eq!(dashboard.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn the_subscription_budget_does_not_block_admin_logins() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.rate_limit.per_ip = limit(1)).await;
+    app.mock_email_server().await;
+    app.post_subscriptions("name=le%20guin&email=ursula%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let over_limit = app
+        .post_subscriptions("name=le%20guin&email=le_guin%40gmail.com".into())
+        .await;
+
+    // Act
+    let response = app.post_login("admin", "guessing-the-password").await;
+
+    // Assert
+    assert_eq!(over_limit.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
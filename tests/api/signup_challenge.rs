@@ -0,0 +1,182 @@
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::signup_challenge::solve;
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn signups_filling_in_the_honeypot_are_silently_dropped() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&website=http%3A%2F%2Fspam.example"
+                .into(),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 0);
+}
+
+#[tokio::test]
+async fn an_empty_honeypot_is_fine() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com&website=".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn there_is_no_challenge_unless_configured() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_signup_challenge().await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn signups_without_the_challenge_are_silently_dropped() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.signup_challenge.proof_of_work_bits = Some(8)).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 0);
+}
+
+#[tokio::test]
+async fn signups_with_a_solved_challenge_go_through() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.signup_challenge.proof_of_work_bits = Some(8)).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let challenge: serde_json::Value = app.get_signup_challenge().await.json().await.unwrap();
+    let token = challenge["token"].as_str().unwrap();
+    assert_eq!(challenge["proof_of_work_bits"], 8);
+    let solution = solve(token, "ursula_le_guin@gmail.com", 8);
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", app.address))
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("challenge", token),
+            ("proof_of_work", &solution),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn a_solved_challenge_cannot_be_reused_for_another_address() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.signup_challenge.proof_of_work_bits = Some(8)).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let challenge: serde_json::Value = app.get_signup_challenge().await.json().await.unwrap();
+    let token = challenge["token"].as_str().unwrap();
+    let solution = solve(token, "ursula_le_guin@gmail.com", 8);
+
+    // Act
+    for email in ["ursula_le_guin@gmail.com", "octavia_butler@gmail.com"] {
+        app.api_client
+            .post(format!("{}/subscriptions", app.address))
+            .form(&[
+                ("name", "le guin"),
+                ("email", email),
+                ("challenge", token),
+                ("proof_of_work", &solution),
+            ])
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    // Assert
+    assert_eq!(app.subscriber_count().await, 1);
+}
+
+#[tokio::test]
+async fn signups_submitted_too_fast_are_silently_dropped() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.signup_challenge.min_fill_seconds = Some(60)).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let challenge: serde_json::Value = app.get_signup_challenge().await.json().await.unwrap();
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", app.address))
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("challenge", challenge["token"].as_str().unwrap()),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 0);
+}
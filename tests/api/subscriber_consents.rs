@@ -0,0 +1,143 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestApp, TestUser};
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+/// Subscribe and confirm from a browser, filling in the consent fields of the
+/// signup form, returning the subscriber id.
+///
+/// `TestApp::confirmed_subscriber` sends neither a user agent nor these
+/// fields, which are what the consent records keep.
+async fn subscribe_from_browser(app: &TestApp) -> Uuid {
+    app.mock_email_server().await;
+    let browser = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .build()
+        .unwrap();
+
+    browser
+        .post(format!("{}/subscriptions", app.address))
+        .form(&[
+            ("name", "le guin"),
+            ("email", EMAIL),
+            ("source", "homepage-footer"),
+            ("consent_text_version", "2025-03"),
+        ])
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let token = app.subscription_token(EMAIL).await;
+    browser
+        .post(format!("{}/subscriptions/confirm", app.address))
+        .form(&[("subscription_token", token)])
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    app.subscriber_id(EMAIL).await
+}
+
+#[tokio::test]
+async fn subscribing_and_confirming_are_both_recorded_as_consents() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = subscribe_from_browser(&app).await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_consents(subscriber_id).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let consents: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(consents.len(), 2);
+    assert_eq!(consents[0]["event"], "subscribe");
+    assert_eq!(consents[1]["event"], "confirm");
+    for consent in &consents {
+        assert_eq!(consent["ip_address"], "127.0.0.1");
+        assert_eq!(consent["user_agent"], USER_AGENT);
+        // The confirmation is tied to what the subscriber agreed to on the form.
+        assert_eq!(consent["source"], "homepage-footer");
+        assert_eq!(consent["consent_text_version"], "2025-03");
+    }
+}
+
+#[tokio::test]
+async fn confirming_twice_records_a_single_confirmation() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", EMAIL).await;
+    let subscriber_id = app.subscriber_id(EMAIL).await;
+    let email_request = &app.wait_for_emails(1).await[0];
+    let links = app.get_confirmation_links(email_request).await;
+
+    // Act
+    app.confirm_subscription(&links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let confirmations = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM consents WHERE subscriber_id = $1 AND event = 'confirm'",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(confirmations, Some(1));
+}
+
+#[tokio::test]
+async fn the_consents_of_an_unknown_subscriber_are_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_consents(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn erasure_keeps_the_consents_but_not_where_they_came_from() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = subscribe_from_browser(&app).await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let consents: Vec<serde_json::Value> = app
+        .get_subscriber_consents(subscriber_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(consents.len(), 2);
+    for consent in &consents {
+        assert_eq!(consent["ip_address"], serde_json::Value::Null);
+        assert_eq!(consent["user_agent"], serde_json::Value::Null);
+        assert_eq!(consent["consent_text_version"], "2025-03");
+    }
+}
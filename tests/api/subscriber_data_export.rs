@@ -0,0 +1,168 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestUser};
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+#[tokio::test]
+async fn admins_can_download_everything_held_about_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", EMAIL).await;
+    let subscriber_id = app.subscriber_id(EMAIL).await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_data_export(subscriber_id).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let archive: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(archive["subscriber"]["email"], EMAIL);
+    assert_eq!(archive["subscriber"]["status"], "confirmed");
+    let actions: Vec<_> = archive["status_history"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| change["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["subscription.create", "subscription.confirm"]);
+    let token = archive["subscription_tokens"][0].as_str().unwrap();
+    assert_eq!(token.chars().count(), 5);
+    assert_eq!(archive["consents"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn subscribers_saved_under_older_validation_rules_can_be_exported() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", EMAIL).await;
+    let subscriber_id = app.subscriber_id(EMAIL).await;
+    // An address the parser refuses today, as a row saved before it did.
+    sqlx::query!(
+        "UPDATE subscriptions SET email = 'ursula le guin@example.com' WHERE id = $1",
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_data_export(subscriber_id).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let archive: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(archive["subscriber"]["email"], "ursula le guin@example.com");
+}
+
+#[tokio::test]
+async fn the_admin_export_of_an_unknown_subscriber_is_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_data_export(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn editors_cannot_export_subscriber_data() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", EMAIL).await;
+    let subscriber_id = app.subscriber_id(EMAIL).await;
+    let user = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_data_export(subscriber_id).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn subscribers_receive_a_link_to_download_their_data() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", EMAIL).await;
+    let subscriber_id = app.subscriber_id(EMAIL).await;
+
+    // Act
+    app.post_data_export_request(EMAIL)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.wait_for_emails(2).await[1];
+    let links = app.get_confirmation_links(email_request).await;
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let archive: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(archive["subscriber"]["id"], subscriber_id.to_string());
+    let stored = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM audit_log WHERE action = 'subscriber.data_export' AND actor_type = 'subscriber'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(stored, Some(1));
+}
+
+#[tokio::test]
+async fn requesting_an_export_for_an_unknown_address_sends_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_data_export_request("nobody@example.com").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn an_invalid_export_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/data-export?token=not-a-token",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
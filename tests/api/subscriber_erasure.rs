@@ -0,0 +1,240 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp, TestUser};
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+/// Spawn the application with a confirmed subscriber and an owner logged in.
+async fn spawn_app_with_subscriber() -> (TestApp, Uuid) {
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", EMAIL).await;
+    let subscriber_id = app.subscriber_id(EMAIL).await;
+
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    (app, subscriber_id)
+}
+
+#[tokio::test]
+async fn erasure_removes_the_subscribers_personal_data_and_tokens() {
+    // Arrange
+    let (app, subscriber_id) = spawn_app_with_subscriber().await;
+
+    // Act
+    let response = app.delete_subscriber(subscriber_id).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let subscriber = sqlx::query!(
+        "SELECT email, name, status, erased_at FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(!subscriber.email.contains("ursula"));
+    assert_eq!(subscriber.name, "");
+    assert_eq!(subscriber.status, "erased");
+    assert!(subscriber.erased_at.is_some());
+    let tokens = sqlx::query_scalar!("SELECT COUNT(*) FROM subscription_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens, Some(0));
+    let suppressed = sqlx::query_scalar!("SELECT email_hash FROM suppressed_emails")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!suppressed.contains("ursula"));
+}
+
+#[tokio::test]
+async fn erasure_is_recorded_in_the_audit_log() {
+    // Arrange
+    let (app, subscriber_id) = spawn_app_with_subscriber().await;
+
+    // Act
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+    // A second erasure has nothing left to do.
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let entries = sqlx::query!(
+        "SELECT before, after FROM audit_log WHERE action = 'subscriber.erase' AND target_id = $1",
+        subscriber_id.to_string()
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].before,
+        Some(serde_json::json!({ "status": "confirmed" }))
+    );
+    assert_eq!(
+        entries[0].after,
+        Some(serde_json::json!({ "status": "erased" }))
+    );
+}
+
+#[tokio::test]
+async fn erasing_an_unknown_subscriber_is_a_404() {
+    // Arrange
+    let (app, _) = spawn_app_with_subscriber().await;
+
+    // Act
+    let response = app.delete_subscriber(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn an_erased_address_cannot_subscribe_again() {
+    // Arrange
+    let (app, subscriber_id) = spawn_app_with_subscriber().await;
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=Ursula_Le_Guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+    // Only the confirmation email of the original subscription was sent.
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+/// Subscribe `email` and have an owner erase the new subscriber.
+async fn subscribe_and_erase(app: &TestApp, email: &str) {
+    app.mock_email_server().await;
+    app.confirmed_subscriber("le guin", email).await;
+    let subscriber_id = app.subscriber_id(email).await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn an_erased_address_cannot_subscribe_again_with_its_domain_in_punycode() {
+    // Arrange
+    let app = spawn_app().await;
+    subscribe_and_erase(&app, "ursula@Bücher.example").await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40xn--bcher-kva.example".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn an_erased_address_cannot_subscribe_again_through_a_provider_alias() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.email_normalisation.fold_provider_aliases = true).await;
+    subscribe_and_erase(&app, "ursula.le.guin@gmail.com").await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursulaleguin%2Bnews%40googlemail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.subscriber_count().await, 1);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn an_erased_address_is_rejected_by_imports() {
+    // Arrange
+    let (app, subscriber_id) = spawn_app_with_subscriber().await;
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let summary: serde_json::Value = app
+        .post_subscriber_import(
+            "email,name,consent_basis\n\
+            ursula_le_guin@gmail.com,Ursula,confirmed\n\
+            octavia@example.com,Octavia Butler,confirmed\n"
+                .into(),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(summary["imported"], 1);
+    assert_eq!(summary["failed"], 1);
+}
+
+#[tokio::test]
+async fn erased_subscribers_still_count_towards_delivery_statistics() {
+    // Arrange
+    let (app, subscriber_id) = spawn_app_with_subscriber().await;
+    let user_id = sqlx::query_scalar!("SELECT user_id FROM users")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO newsletter_issues (issue_id, title, text_content, html_content, list_id, created_by, created_at, published_at)
+        SELECT $1, 'Issue', 'text', '<p>html</p>', list_id, $2, now(), now() FROM lists WHERE is_default",
+        issue_id,
+        user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO issue_deliveries (issue_id, subscriber_id, outcome, attempted_at)
+        VALUES ($1, $2, 'sent', now())",
+        issue_id,
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    app.delete_subscriber(subscriber_id)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let deliveries = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM issue_deliveries WHERE issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deliveries, Some(1));
+}
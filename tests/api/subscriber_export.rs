@@ -0,0 +1,121 @@
+use reqwest::StatusCode;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, spawn_app_with_subscribers, TestUser};
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv_by_default() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    let response = app.get_subscriber_export("").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "id,email,name,status,subscribed_at,confirmed_at");
+    assert!(body.contains(",ursula@example.com,\"Le Guin, Ursula\",confirmed,"));
+    // Ted has not confirmed yet, so the last column is empty.
+    assert!(lines.iter().any(
+        |l| l.contains(",ted@example.org,Ted Chiang,pending_confirmation,") && l.ends_with(',')
+    ));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_json_lines() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    let response = app.get_subscriber_export("format=jsonl").await;
+
+    // Assert
+    assert_eq!(response.headers()["Content-Type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let subscribers: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(subscribers.len(), 5);
+    let ted = subscribers
+        .iter()
+        .find(|s| s["email"] == "ted@example.org")
+        .unwrap();
+    assert_eq!(ted["status"], "pending_confirmation");
+    assert!(ted["confirmed_at"].is_null());
+    let ursula = subscribers
+        .iter()
+        .find(|s| s["email"] == "ursula@example.com")
+        .unwrap();
+    assert!(ursula["subscribed_at"].is_string());
+    assert!(ursula["confirmed_at"].is_string());
+}
+
+#[tokio::test]
+async fn exports_use_the_search_filters() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    let body = app
+        .get_subscriber_export("format=jsonl&status=confirmed&email=octavia")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    let subscribers: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "octavia@example.com");
+}
+
+#[tokio::test]
+async fn exports_are_recorded_in_the_audit_log_with_their_filters() {
+    // Arrange
+    let app = spawn_app_with_subscribers().await;
+
+    // Act
+    app.get_subscriber_export("status=confirmed&email=octavia")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    let after =
+        sqlx::query_scalar!("SELECT after FROM audit_log WHERE action = 'subscriber.export'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(after["format"], "csv");
+    assert_eq!(after["filters"]["status"], "confirmed");
+    // The search term itself could be an address, which the log must not hold.
+    assert_eq!(after["filters"]["email"], true);
+    assert_eq!(after["filters"]["name"], false);
+}
+
+#[tokio::test]
+async fn only_owners_can_export_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Viewer).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.get_subscriber_export("").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
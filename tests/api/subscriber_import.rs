@@ -0,0 +1,223 @@
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, TestApp, TestUser};
+
+async fn spawn_app_as(role: Role) -> TestApp {
+    let app = spawn_app().await;
+    let user = TestUser::generate(role).store(&app.db_pool).await;
+    app.login(&user).await;
+    app
+}
+
+#[tokio::test]
+async fn valid_rows_are_imported_and_invalid_ones_reported() {
+    // Arrange
+    let app = spawn_app_as(Role::Owner).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let csv = "email,name,consent_basis\n\
+        ursula@example.com,Ursula Le Guin,confirmed\n\
+        octavia@example.com,\"Butler, Octavia\",unconfirmed\n\
+        not-an-email,Ted Chiang,confirmed\n\
+        ursula@example.com,Ursula again,confirmed\n\
+        iain@example.com,Iain Banks,implied\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv.into()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let summary: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(summary["imported"], 2);
+    assert_eq!(summary["failed"], 3);
+
+    let subscribers = sqlx::query!("SELECT email, name, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 2);
+    assert_eq!(subscribers[0].name, "Butler, Octavia");
+    assert_eq!(subscribers[0].status, "pending_confirmation");
+    assert_eq!(subscribers[1].status, "confirmed");
+    app.wait_for_emails(1).await;
+
+    let report = app
+        .api_client
+        .get(format!(
+            "{}{}",
+            app.address,
+            summary["error_report"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(report.status(), StatusCode::OK);
+    assert_eq!(report.headers()["Content-Type"], "text/csv; charset=utf-8");
+    assert_eq!(
+        report.text().await.unwrap(),
+        "line,error\n\
+        4,\"invalid email address\"\n\
+        5,\"duplicate of line 2\"\n\
+        6,\"consent_basis must be either confirmed or unconfirmed\"\n"
+    );
+}
+
+#[tokio::test]
+async fn addresses_differing_only_in_the_case_of_their_domain_are_duplicates() {
+    // Arrange
+    let app = spawn_app_as(Role::Owner).await;
+    app.post_subscriber_import(
+        "email,name,consent_basis\nursula@example.com,Ursula,confirmed\n".into(),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    let csv = "email,name,consent_basis\n\
+        octavia@example.com,Octavia Butler,confirmed\n\
+        octavia@Example.com,Octavia again,confirmed\n\
+        ursula@EXAMPLE.com,Ursula Le Guin,confirmed\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv.into()).await;
+
+    // Assert
+    let summary: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(summary["imported"], 2);
+    assert_eq!(summary["failed"], 1);
+    let subscribers = sqlx::query!("SELECT name FROM subscriptions ORDER BY normalised_email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 2);
+    assert_eq!(subscribers[0].name, "Octavia Butler");
+    assert_eq!(subscribers[1].name, "Ursula Le Guin");
+}
+
+#[tokio::test]
+async fn existing_subscribers_are_updated_but_stay_confirmed() {
+    // Arrange
+    let app = spawn_app_as(Role::Owner).await;
+    app.post_subscriber_import(
+        "email,name,consent_basis\nursula@example.com,Ursula,confirmed\n".into(),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Act
+    app.post_subscriber_import(
+        "email,name,consent_basis\nursula@example.com,Ursula Le Guin,unconfirmed\n".into(),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Assert
+    let subscriber = sqlx::query!("SELECT name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.name, "Ursula Le Guin");
+    assert_eq!(subscriber.status, "confirmed");
+}
+
+#[tokio::test]
+async fn large_files_are_imported_in_batches() {
+    // Arrange
+    let app = spawn_app_as(Role::Owner).await;
+    let mut csv = String::from("email,name,consent_basis\n");
+    for i in 0..1234 {
+        csv.push_str(&format!(
+            "subscriber{}@example.com,Subscriber {},confirmed\n",
+            i, i
+        ));
+    }
+
+    // Act
+    let summary: serde_json::Value = app
+        .post_subscriber_import(csv)
+        .await
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(summary["imported"], 1234);
+    assert_eq!(app.subscriber_count().await, 1234);
+    let import =
+        sqlx::query!("SELECT imported_rows, failed_rows, completed_at FROM subscriber_imports")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(import.imported_rows, 1234);
+    assert_eq!(import.failed_rows, 0);
+    assert!(import.completed_at.is_some());
+}
+
+#[tokio::test]
+async fn each_malformed_row_is_reported_on_its_own_line() {
+    // Arrange
+    let app = spawn_app_as(Role::Owner).await;
+    let mut csv = b"email,name,consent_basis\n".to_vec();
+    csv.extend_from_slice(b"\xffursula@example.com,Ursula Le Guin,confirmed\n");
+    csv.extend_from_slice(b"\xfeoctavia@example.com,Octavia Butler,confirmed\n");
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/imports", app.address))
+        .header("Content-Type", "text/csv")
+        .body(csv)
+        .send()
+        .await
+        .expect("Failed to send request.");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let summary: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(summary["failed"], 2);
+    let lines = sqlx::query_scalar!("SELECT line FROM subscriber_import_errors ORDER BY line")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(lines, [2, 3]);
+}
+
+#[tokio::test]
+async fn files_without_the_required_columns_are_rejected() {
+    // Arrange
+    let app = spawn_app_as(Role::Owner).await;
+
+    // Act
+    let response = app
+        .post_subscriber_import("email,name\nursula@example.com,Ursula\n".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn only_owners_can_import_subscribers() {
+    // Arrange
+    let app = spawn_app_as(Role::Editor).await;
+
+    // Act
+    let response = app
+        .post_subscriber_import("email,name,consent_basis\n".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
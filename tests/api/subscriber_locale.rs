@@ -0,0 +1,155 @@
+use reqwest::StatusCode;
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn subscribe_with_language(app: &TestApp, body: &str, accept_language: &str) {
+    app.api_client
+        .post(format!("{}/subscriptions", app.address))
+        .header("Accept-Language", accept_language)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body.to_owned())
+        .send()
+        .await
+        .expect("Failed to send request.")
+        .error_for_status()
+        .unwrap();
+}
+
+async fn last_subject(app: &TestApp) -> String {
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["subject"].as_str().unwrap().to_owned()
+}
+
+async fn saved_locale(app: &TestApp) -> Option<String> {
+    sqlx::query_scalar!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("failed to fetch saved subscription.")
+}
+
+#[tokio::test]
+async fn the_confirmation_email_is_written_in_the_browser_language() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+
+    // Act
+    subscribe_with_language(
+        &app,
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "de;q=0.9, fr-CA, en;q=0.5",
+    )
+    .await;
+
+    // Assert
+    assert_eq!(last_subject(&app).await, "Bienvenue !");
+    assert_eq!(saved_locale(&app).await.as_deref(), Some("fr"));
+}
+
+#[tokio::test]
+async fn a_locale_picked_in_the_form_wins_over_the_browser_language() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+
+    // Act
+    subscribe_with_language(
+        &app,
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&locale=en",
+        "fr",
+    )
+    .await;
+
+    // Assert
+    assert_eq!(last_subject(&app).await, "Welcome!");
+    assert_eq!(saved_locale(&app).await.as_deref(), Some("en"));
+}
+
+#[tokio::test]
+async fn unsupported_languages_fall_back_to_the_default_locale() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+
+    // Act
+    subscribe_with_language(
+        &app,
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "ja",
+    )
+    .await;
+
+    // Assert
+    assert_eq!(last_subject(&app).await, "Welcome!");
+    assert_eq!(saved_locale(&app).await, None);
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_locale() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let token = app.subscription_token("ursula_le_guin@gmail.com").await;
+
+    // Act
+    let response = app.post_locale_change(&token, "fr").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(saved_locale(&app).await.as_deref(), Some("fr"));
+    let entry = sqlx::query!(
+        "SELECT actor_type, before, after FROM audit_log WHERE action = 'subscriber.locale_change'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(entry.actor_type, "subscriber");
+    assert!(entry.before.unwrap()["locale"].is_null());
+    assert_eq!(entry.after.unwrap()["locale"], "fr");
+
+    app.post_data_export_request("ursula_le_guin@gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let emails = app.wait_for_emails(2).await;
+    let body: serde_json::Value = serde_json::from_slice(&emails[1].body).unwrap();
+    assert_eq!(body["subject"], "Vos données");
+}
+
+#[tokio::test]
+async fn changing_to_an_unsupported_locale_is_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    app.mock_email_server().await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let token = app.subscription_token("ursula_le_guin@gmail.com").await;
+
+    // Act
+    let response = app.post_locale_change(&token, "klingon").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(saved_locale(&app).await, None);
+}
+
+#[tokio::test]
+async fn changing_the_locale_with_an_unknown_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_locale_change("unknown", "fr").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -4,7 +4,7 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn subscribe_returns_200_for_valid_form_data() {
@@ -78,6 +78,59 @@ async fn subscribe_returns_400_when_fields_are_present_but_invalid() {
     }
 }
 
+#[tokio::test]
+async fn subscribe_says_which_field_is_invalid_and_why() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = [
+        ("name=&email=ursula_le_guin%40gmail.com", "name", "empty"),
+        (
+            "name=visit%20cheap-pills.com&email=ursula_le_guin%40gmail.com",
+            "name",
+            "looks_like_url",
+        ),
+        (
+            "name=le%20guin&email=definitely-not-an-email",
+            "email",
+            "invalid",
+        ),
+    ];
+
+    for (body, field, code) in test_cases {
+        // Act
+        let response = app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["errors"][0]["field"], field);
+        assert_eq!(body["errors"][0]["code"], code);
+        assert!(body["errors"][0]["message"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn subscribe_reports_every_invalid_field_at_once() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=&email=definitely-not-an-email".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let fields: Vec<_> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["name", "email"]);
+}
+
 #[tokio::test]
 async fn subscribe_returns_422_when_data_is_missing() {
     // Arrange
@@ -139,7 +192,108 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
 
     // Assert
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request).await;
+    let confirmation_links = app.get_confirmation_links(email_request).await;
 
     assert_eq!(confirmation_links.html, confirmation_links.text);
 }
+
+#[tokio::test]
+async fn the_confirmation_email_greets_the_subscriber_by_name() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["subject"], "Welcome!");
+    assert!(body["text"].as_str().unwrap().contains("le guin"));
+    assert!(body["html"].as_str().unwrap().contains("le guin!<br />"));
+}
+
+#[tokio::test]
+async fn subscribing_again_with_a_differently_cased_domain_updates_the_pending_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions("name=le%20guin&email=ursula%40Example.COM".into())
+        .await;
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT email, normalised_email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "ursula@example.com");
+    assert_eq!(saved[0].normalised_email, "ursula@example.com");
+}
+
+#[tokio::test]
+async fn gmail_aliases_are_the_same_subscriber_when_folding_is_enabled() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.email_normalisation.fold_provider_aliases = true).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions("name=le%20guin&email=ursula.leguin%40gmail.com".into())
+        .await;
+    app.post_subscriptions("name=le%20guin&email=UrsulaLeGuin%2Bnews%40googlemail.com".into())
+        .await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT normalised_email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].normalised_email, "ursulaleguin@gmail.com");
+}
+
+#[tokio::test]
+async fn gmail_aliases_are_different_subscribers_by_default() {
+    // Arrange
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions("name=le%20guin&email=ursula.leguin%40gmail.com".into())
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursulaleguin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(app.subscriber_count().await, 2);
+}
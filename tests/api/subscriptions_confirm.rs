@@ -1,7 +1,28 @@
 use reqwest::StatusCode;
 use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with, TestApp, ConfirmationLinks};
+
+async fn subscribe(app: &TestApp) -> ConfirmationLinks {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_links(email_request).await
+}
+
+async fn saved_status(app: &TestApp) -> String {
+    sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("failed to fetch saved subscription.")
+}
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -21,17 +42,7 @@ async fn confirmations_without_token_are_rejected_with_a_400() {
 async fn the_link_returned_by_subscribe_returns_a_200_if_called() {
     // Arrange
     let app = spawn_app().await;
-    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
-
-    Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .mount(&app.email_server)
-        .await;
-
-    app.post_subscriptions(body.into()).await;
-    let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(email_request).await;
+    let confirmation_links = subscribe(&app).await;
 
     // Act
     let response = reqwest::get(confirmation_links.html).await.unwrap();
@@ -41,24 +52,60 @@ async fn the_link_returned_by_subscribe_returns_a_200_if_called() {
 }
 
 #[tokio::test]
-async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
+async fn the_link_leads_to_a_page_that_submits_the_token() {
     // Arrange
     let app = spawn_app().await;
-    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let confirmation_links = subscribe(&app).await;
+    let token = confirmation_links.html
+        .query_pairs()
+        .find(|(k, _)| k == "subscription_token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap();
 
-    Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .mount(&app.email_server)
-    .await;
+    // Act
+    let page = reqwest::get(confirmation_links.html).await.unwrap().text().await.unwrap();
 
-    app.post_subscriptions(body.into()).await;
-    let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(email_request).await;
+    // Assert
+    assert!(page.contains(r#"<form action="/subscriptions/confirm" method="post">"#));
+    assert!(page.contains(&format!(r#"value="{}""#, token)));
+}
+
+#[tokio::test]
+async fn following_the_link_alone_does_not_confirm_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = subscribe(&app).await;
 
     // Act
     reqwest::get(confirmation_links.html).await.unwrap().error_for_status().unwrap();
 
+    // Assert
+    assert_eq!(saved_status(&app).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn the_link_with_an_unknown_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/subscriptions/confirm?subscription_token=unknown", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn submitting_the_confirmation_page_confirms_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = subscribe(&app).await;
+
+    // Act
+    app.confirm_subscription(&confirmation_links.html).await.error_for_status().unwrap();
+
     // Assert
     let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
         .fetch_one(&app.db_pool)
@@ -69,3 +116,130 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "confirmed");
 }
+
+#[tokio::test]
+async fn confirming_twice_is_harmless() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = subscribe(&app).await;
+    app.confirm_subscription(&confirmation_links.html).await.error_for_status().unwrap();
+
+    // Act
+    let response = app.confirm_subscription(&confirmation_links.html).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(saved_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn confirming_shows_a_thank_you_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = subscribe(&app).await;
+
+    // Act
+    let response = app.confirm_subscription(&confirmation_links.html).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("Your subscription is confirmed"));
+}
+
+#[tokio::test]
+async fn the_link_of_a_confirmed_subscriber_says_so() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = subscribe(&app).await;
+    app.confirm_subscription(&confirmation_links.html).await.error_for_status().unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("You are already subscribed"));
+}
+
+#[tokio::test]
+async fn an_unknown_token_is_explained_on_a_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/subscriptions/confirm?subscription_token=unknown", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.text().await.unwrap().contains("This link is not valid"));
+}
+
+#[tokio::test]
+async fn expired_tokens_cannot_confirm_a_subscriber() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.confirmation.token_lifetime_hours = 0).await;
+    let confirmation_links = subscribe(&app).await;
+
+    // Act
+    let page = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+    let response = app.confirm_subscription(&confirmation_links.html).await;
+
+    // Assert
+    assert_eq!(page.status(), StatusCode::GONE);
+    assert!(page.text().await.unwrap().contains("This link has expired"));
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(saved_status(&app).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribing_again_before_confirming_sends_a_new_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let first_links = subscribe(&app).await;
+
+    // Act
+    app.post_subscriptions("name=ursula&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email_request = &app.wait_for_emails(2).await[1];
+    let second_links = app.get_confirmation_links(email_request).await;
+    assert_ne!(first_links.html, second_links.html);
+    app.confirm_subscription(&second_links.html).await.error_for_status().unwrap();
+    let saved = sqlx::query!("SELECT name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "ursula");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn outcomes_redirect_where_configured() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.confirmation.redirects.confirmed = Some("https://example.com/welcome".into());
+        c.application.confirmation.redirects.invalid = Some("https://example.com/oops".into());
+    })
+    .await;
+    let confirmation_links = subscribe(&app).await;
+
+    // Act
+    let confirmed = app.confirm_subscription(&confirmation_links.html).await;
+    let invalid = app
+        .api_client
+        .get(format!("{}/subscriptions/confirm?subscription_token=unknown", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(confirmed.status(), StatusCode::SEE_OTHER);
+    assert_eq!(confirmed.headers()["Location"], "https://example.com/welcome");
+    assert_eq!(invalid.status(), StatusCode::SEE_OTHER);
+    assert_eq!(invalid.headers()["Location"], "https://example.com/oops");
+}
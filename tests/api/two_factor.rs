@@ -0,0 +1,215 @@
+use reqwest::StatusCode;
+use zero2prod::authorization::Role;
+
+use crate::helpers::{spawn_app, totp_code, TestUser};
+
+#[tokio::test]
+async fn enrollment_returns_a_secret_an_otpauth_uri_and_a_qr_code() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let response = app.post_two_factor_enrollment().await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let secret = body["secret"].as_str().unwrap();
+    let otpauth_uri = body["otpauth_uri"].as_str().unwrap();
+    assert!(otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(otpauth_uri.contains(secret));
+    assert!(body["qr_code_svg"].as_str().unwrap().contains("<svg"));
+}
+
+#[tokio::test]
+async fn enrollment_is_not_active_until_confirmed_with_a_valid_code() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&user).await;
+    app.post_two_factor_enrollment().await;
+
+    // Act
+    let response = app.post_two_factor_enrollment_confirmation("000000").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let login_response: serde_json::Value = app
+        .post_login(&user.username, &user.password)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(login_response["two_factor_required"], false);
+}
+
+#[tokio::test]
+async fn confirming_enrollment_returns_ten_recovery_codes() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&user).await;
+
+    // Act
+    let (_, recovery_codes) = app.enroll_two_factor().await;
+
+    // Assert
+    assert_eq!(recovery_codes.len(), 10);
+    let stored = sqlx::query!(
+        "SELECT code_hash FROM recovery_codes WHERE user_id = $1",
+        user.user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(stored.len(), 10);
+    assert!(stored
+        .iter()
+        .all(|s| !recovery_codes.contains(&s.code_hash)));
+}
+
+#[tokio::test]
+async fn enrolled_users_need_a_second_factor_to_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    let (secret, _) = app.enroll_two_factor().await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Password only
+    let login_response: serde_json::Value = app
+        .post_login(&user.username, &user.password)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - Part 1
+    assert_eq!(login_response["two_factor_required"], true);
+    assert_eq!(
+        app.get_admin_users().await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Act - Part 2 - Second factor
+    let response = app.post_login_two_factor(&totp_code(&secret, 1)).await;
+
+    // Assert - Part 2
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.get_admin_users().await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn totp_codes_cannot_be_replayed() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    let (secret, _) = app.enroll_two_factor().await;
+    let code = totp_code(&secret, 1);
+    app.post_logout().await;
+    app.post_login(&user.username, &user.password).await;
+    app.post_login_two_factor(&code)
+        .await
+        .error_for_status()
+        .unwrap();
+    app.post_logout().await;
+    app.post_login(&user.username, &user.password).await;
+
+    // Act
+    let response = app.post_login_two_factor(&code).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn recovery_codes_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    let (_, recovery_codes) = app.enroll_two_factor().await;
+    app.post_logout().await;
+
+    // Act - Part 1 - First use
+    app.post_login(&user.username, &user.password).await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+
+    // Assert - Part 1
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Act - Part 2 - Second use
+    app.post_logout().await;
+    app.post_login(&user.username, &user.password).await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+
+    // Assert - Part 2
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_end_the_pending_session() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&user).await;
+    let (secret, _) = app.enroll_two_factor().await;
+    app.post_logout().await;
+    app.post_login(&user.username, &user.password).await;
+
+    // Act
+    for _ in 0..5 {
+        let response = app.post_login_two_factor("000000").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    let response = app.post_login_two_factor(&totp_code(&secret, 1)).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn the_policy_restricts_users_without_a_second_factor_to_enrolling() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    let other_owner = TestUser::generate(Role::Owner).store(&app.db_pool).await;
+    app.login(&owner).await;
+    app.enroll_two_factor().await;
+    app.put_security_policy(true)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act - Part 1 - Not enrolled
+    app.login(&other_owner).await;
+    let response = app.get_admin_users().await;
+
+    // Assert - Part 1
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Act - Part 2 - Enrolled
+    app.enroll_two_factor().await;
+    let response = app.get_admin_users().await;
+
+    // Assert - Part 2
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn only_owners_can_change_the_security_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate(Role::Editor).store(&app.db_pool).await;
+    app.login(&editor).await;
+
+    // Act
+    let response = app.put_security_policy(true).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}